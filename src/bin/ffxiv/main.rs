@@ -0,0 +1,393 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::{Mutex, PoisonError},
+};
+
+use clap::{Parser, Subcommand, ValueEnum};
+use ffxiv_parser_lib::{
+    Error, ExcelDataRow, ExcelDataType, ExcelLanguage, ExtractReport, FfxivLibrary, FileKey,
+    GameInstallation, MemorySource, OverlayLayer, PathDictionary, SqPackIndexHash, ZipSource,
+};
+use serde_json::{json, Value};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+//////////////////////////////////////////
+
+/// Browse & extract FINAL FANTASY XIV game data.
+#[derive(Parser)]
+#[command(name = "ffxiv", version)]
+struct Cli {
+    /// The `sqpack` directory, the `game` directory above it, or a zip or tar archive of either.
+    /// Defaults to the first installation found.
+    #[arg(short, long, global = true)]
+    game: Option<PathBuf>,
+
+    /// A list of known paths, one per line, used to name index entries.
+    #[arg(short, long, global = true)]
+    dictionary: Option<PathBuf>,
+
+    /// A directory of loose files, read before the SqPack files. May be repeated, the first
+    /// directory taking precedence.
+    #[arg(long, global = true)]
+    overlay: Vec<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Show the platform, versions & indexes of the game data.
+    Info,
+
+    /// List the files of every index, or of the given ones, e.g. `0a0000`.
+    Ls {
+        indexes: Vec<String>,
+
+        /// Also show where each file is stored.
+        #[arg(short, long)]
+        long: bool,
+    },
+
+    /// Write a file to standard output.
+    Cat { path: String },
+
+    /// Write files below a directory, keeping their paths.
+    Extract {
+        /// Paths, or patterns like `exd/*.exh` matched against the paths the dictionary knows.
+        patterns: Vec<String>,
+
+        /// Extract every file of an index, e.g. `0a0000`, naming unknown ones by their hash.
+        #[arg(short, long)]
+        index: Vec<String>,
+
+        #[arg(short, long, default_value = ".")]
+        output: PathBuf,
+    },
+
+    /// Dump an Excel sheet, e.g. `Item` or `exd/Item`.
+    Exd {
+        sheet: String,
+
+        #[arg(short, long, value_enum, default_value_t = Format::Csv)]
+        format: Format,
+
+        #[arg(short, long, value_enum, default_value_t = Language::En)]
+        language: Language,
+
+        /// Write to a file rather than to standard output.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Print the index file & the `.index` & `.index2` hashes of paths.
+    Hash {
+        #[arg(required = true)]
+        paths: Vec<String>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Csv,
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Language {
+    None,
+    Ja,
+    En,
+    De,
+    Fr,
+    Chs,
+    Cht,
+    Ko,
+}
+
+impl From<Language> for ExcelLanguage {
+    fn from(value: Language) -> Self {
+        match value {
+            Language::None => ExcelLanguage::None,
+            Language::Ja => ExcelLanguage::Japanese,
+            Language::En => ExcelLanguage::English,
+            Language::De => ExcelLanguage::German,
+            Language::Fr => ExcelLanguage::French,
+            Language::Chs => ExcelLanguage::ChineseSimplified,
+            Language::Cht => ExcelLanguage::ChineseTraditional,
+            Language::Ko => ExcelLanguage::Korean,
+        }
+    }
+}
+
+//////////////////////////////////////////
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(&cli) {
+        Ok(code) => code,
+        // Output piped into e.g. `head` stops being read, which isn't a failure
+        Err(e) if is_broken_pipe(e.as_ref()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("ffxiv: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn is_broken_pipe(error: &(dyn std::error::Error + 'static)) -> bool {
+    let io_error = match error.downcast_ref::<Error>() {
+        Some(Error::Io(e)) => Some(e),
+        _ => error.downcast_ref::<std::io::Error>(),
+    };
+    io_error.is_some_and(|e| e.kind() == std::io::ErrorKind::BrokenPipe)
+}
+
+fn run(cli: &Cli) -> Result<ExitCode> {
+    // Hashing doesn't need any game data, so it's only opened by the other commands
+    let library = || open_library(cli);
+    match &cli.command {
+        Command::Info => info(&library()?)?,
+        Command::Ls { indexes, long } => ls(&library()?, indexes, *long)?,
+        Command::Cat { path } => {
+            let file = library()?.get_file(path)?;
+            let mut stdout = std::io::stdout().lock();
+            stdout.write_all(&file)?;
+            stdout.flush()?;
+        }
+        Command::Extract {
+            patterns,
+            index,
+            output,
+        } => {
+            let report = extract(&library()?, patterns, index, output)?;
+            for (path, e) in &report.failures {
+                eprintln!("{}: {}", path, e);
+            }
+            eprintln!(
+                "Wrote {} files, {} bytes, to {}",
+                report.files_written,
+                report.bytes_written,
+                output.display()
+            );
+            if !report.is_ok() {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        Command::Exd {
+            sheet,
+            format,
+            language,
+            output,
+        } => {
+            let sheet = sheet.trim_end_matches(".exh");
+            let sheet = if sheet.starts_with("exd/") {
+                sheet.to_string()
+            } else {
+                format!("exd/{}", sheet)
+            };
+            let rows = library()?.get_table_data_in(&sheet, (*language).into())?;
+            match output {
+                Some(output) => write_rows(&rows, *format, File::create(output)?)?,
+                None => write_rows(&rows, *format, std::io::stdout().lock())?,
+            }
+        }
+        Command::Hash { paths } => hash(paths)?,
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Opens the game data given on the command line, with its dictionary & overlays.
+fn open_library(cli: &Cli) -> Result<FfxivLibrary> {
+    let mut library = match &cli.game {
+        None => GameInstallation::discover()
+            .into_iter()
+            .next()
+            .ok_or("no game installation found, pass one with --game")?
+            .library(),
+        Some(path) if path.join("sqpack").is_dir() => FfxivLibrary::new(path.join("sqpack")),
+        Some(path) if path.is_dir() => FfxivLibrary::new(path),
+        Some(path) => open_archive(path)?,
+    };
+
+    if let Some(dictionary) = &cli.dictionary {
+        library.set_path_dictionary(PathDictionary::from_file(dictionary)?);
+    }
+    for overlay in &cli.overlay {
+        library.add_overlay(OverlayLayer::from_directory(overlay));
+    }
+    Ok(library)
+}
+
+fn open_archive(path: &Path) -> Result<FfxivLibrary> {
+    let file_name = path
+        .file_name()
+        .map(|file_name| file_name.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    if file_name.ends_with(".zip") {
+        Ok(FfxivLibrary::from_source(ZipSource::from_file(path)?))
+    } else if file_name.ends_with(".tar.gz") || file_name.ends_with(".tgz") {
+        let reader = flate2::read::GzDecoder::new(BufReader::new(File::open(path)?));
+        Ok(FfxivLibrary::from_source(MemorySource::from_tar(reader)?))
+    } else if file_name.ends_with(".tar") {
+        let reader = BufReader::new(File::open(path)?);
+        Ok(FfxivLibrary::from_source(MemorySource::from_tar(reader)?))
+    } else {
+        Err(format!("{} isn't a directory, zip or tar archive", path.display()).into())
+    }
+}
+
+/// Parses an index's key, e.g. `0a0000`, or a file name like `0a0000.win32.index`.
+fn parse_file_key(value: &str) -> Result<FileKey> {
+    FileKey::from_file_name(value).ok_or_else(|| format!("invalid index: {}", value).into())
+}
+
+//////////////////////////////////////////
+
+fn info(library: &FfxivLibrary) -> Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    writeln!(stdout, "Platform: {:?}", library.platform())?;
+    match library.versions() {
+        Ok(versions) => {
+            for (repository, version) in versions {
+                writeln!(stdout, "Version:  {} {}", repository, version)?;
+            }
+        }
+        Err(e) => writeln!(stdout, "Version:  unknown ({})", e)?,
+    }
+
+    writeln!(stdout, "Indexes:")?;
+    for coverage in library.coverage()? {
+        match library.path_dictionary() {
+            Some(_) => writeln!(stdout, "  {}", coverage)?,
+            None => writeln!(
+                stdout,
+                "  {}: {} index, {} index2 entries",
+                coverage.file_key, coverage.index1_entries, coverage.index2_entries
+            )?,
+        }
+    }
+    Ok(())
+}
+
+fn ls(library: &FfxivLibrary, indexes: &[String], long: bool) -> Result<()> {
+    let file_keys = if indexes.is_empty() {
+        library
+            .indexes()?
+            .into_iter()
+            .map(|(file_key, _)| file_key)
+            .collect()
+    } else {
+        indexes
+            .iter()
+            .map(|index| parse_file_key(index))
+            .collect::<Result<Vec<_>>>()?
+    };
+
+    let mut stdout = BufWriter::new(std::io::stdout().lock());
+    for file_key in file_keys {
+        for (path, entry) in library.list_index(file_key)? {
+            if long {
+                writeln!(
+                    stdout,
+                    "{}\tdat{}\t{:#010x}\t{}",
+                    file_key, entry.data_file_id, entry.offset, path
+                )?;
+            } else {
+                writeln!(stdout, "{}", path)?;
+            }
+        }
+    }
+    stdout.flush()?;
+    Ok(())
+}
+
+fn extract(
+    library: &FfxivLibrary,
+    patterns: &[String],
+    indexes: &[String],
+    output: &Path,
+) -> Result<ExtractReport> {
+    if patterns.is_empty() && indexes.is_empty() {
+        return Err("nothing to extract, give paths, patterns or --index".into());
+    }
+
+    let mut paths = Vec::new();
+    let mut globs = Vec::new();
+    for pattern in patterns {
+        if pattern.contains(['*', '?', '[']) {
+            globs.push(glob::Pattern::new(&pattern.to_lowercase())?);
+        } else {
+            paths.push(pattern.clone());
+        }
+    }
+    if !globs.is_empty() {
+        for (file_key, _) in library.indexes()? {
+            let known_paths = library.file_paths(file_key)?;
+            paths.extend(
+                known_paths
+                    .into_iter()
+                    .filter(|path| globs.iter().any(|glob| glob.matches(path))),
+            );
+        }
+    }
+
+    let mut report = library.extract_to_directory(&paths, output);
+    for index in indexes {
+        let report = Mutex::new(&mut report);
+        library.extract_index(parse_file_key(index)?, |path, file| {
+            let mut report = report.lock().unwrap_or_else(PoisonError::into_inner);
+            report.write(output, path, file);
+        })?;
+    }
+    Ok(report)
+}
+
+fn write_rows(rows: &[ExcelDataRow], format: Format, writer: impl Write) -> Result<()> {
+    let mut writer = BufWriter::new(writer);
+    match format {
+        Format::Csv => {
+            for row in rows {
+                writeln!(writer, "{}", row.to_csv())?;
+            }
+        }
+        Format::Json => {
+            let rows = rows
+                .iter()
+                .map(|row| {
+                    let columns = row.iter().map(|cell| match cell {
+                        ExcelDataType::String(v) => json!(v),
+                        ExcelDataType::U64(v) => json!(v),
+                        ExcelDataType::I64(v) => json!(v),
+                        ExcelDataType::F32(v) => json!(v),
+                    });
+                    json!({
+                        "row_id": row.row_info().row_id,
+                        "columns": columns.collect::<Vec<_>>(),
+                    })
+                })
+                .collect::<Vec<_>>();
+            serde_json::to_writer_pretty(&mut writer, &Value::Array(rows))?;
+            writeln!(writer)?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn hash(paths: &[String]) -> Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    for path in paths {
+        let path = path.to_lowercase();
+        let (index1, index2) = SqPackIndexHash::from_path(&path)
+            .ok_or_else(|| format!("not a file path: {}", path))?;
+        let file_key = FileKey::new(&path)?;
+        writeln!(stdout, "{}\t{}\t{:?}\t{:?}", path, file_key, index1, index2)?;
+    }
+    Ok(())
+}
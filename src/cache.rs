@@ -0,0 +1,305 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    mem::{size_of, size_of_val},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+use crate::{
+    excel::{ExcelDataFile, ExcelDataRow, ExcelDataType, ExcelHeaderFile},
+    ffxiv_file::FfxivFile,
+    overlay::FileOrigin,
+};
+
+//////////////////////////////////////////
+
+/// How well a library's cache is doing, see `FfxivLibrary::set_cache_budget`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Number of values dropped to stay within the budget.
+    pub evictions: u64,
+    pub entries: usize,
+    /// Estimated memory used by the cached values.
+    pub bytes: usize,
+    pub budget: usize,
+}
+
+impl CacheStats {
+    /// Share of lookups which were served from the cache, between 0 & 1.
+    pub fn hit_ratio(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / lookups as f64
+        }
+    }
+}
+
+//////////////////////////////////////////
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum CacheKey {
+    File(String),
+    ExcelHeader(String),
+    ExcelData(String),
+}
+
+#[derive(Clone)]
+pub(crate) enum CacheValue {
+    File(FfxivFile, FileOrigin),
+    ExcelHeader(Arc<ExcelHeaderFile>),
+    ExcelData(Arc<ExcelDataFile>),
+}
+
+impl CacheValue {
+    /// Rough number of bytes the value keeps alive.
+    fn size(&self) -> usize {
+        match self {
+            CacheValue::File(file, _) => {
+                size_of::<FfxivFile>()
+                    + size_of::<FileOrigin>()
+                    + file.file_name().len()
+                    + file.len()
+            }
+            CacheValue::ExcelHeader(header) => {
+                size_of::<ExcelHeaderFile>()
+                    + size_of_val(&header.columns[..])
+                    + size_of_val(&header.pages[..])
+                    + size_of_val(&header.languages[..])
+            }
+            CacheValue::ExcelData(data) => {
+                let cells = data.iter().flat_map(|row| row.iter());
+                size_of::<ExcelDataFile>()
+                    + data.len() * size_of::<ExcelDataRow>()
+                    + cells
+                        .map(|cell| match cell {
+                            ExcelDataType::String(value) => size_of_val(cell) + value.len(),
+                            _ => size_of_val(cell),
+                        })
+                        .sum::<usize>()
+            }
+        }
+    }
+}
+
+//////////////////////////////////////////
+
+/// A least-recently-used cache, bounded by the estimated size of its values rather than
+/// their number. Values larger than the whole budget aren't kept.
+pub(crate) struct Cache {
+    inner: Mutex<CacheInner>,
+}
+
+#[derive(Default)]
+struct CacheInner {
+    values: HashMap<CacheKey, CacheEntry>,
+    /// Keys by when they were last used, oldest first.
+    recency: BTreeMap<u64, CacheKey>,
+    tick: u64,
+    stats: CacheStats,
+}
+
+struct CacheEntry {
+    value: CacheValue,
+    size: usize,
+    last_used: u64,
+}
+
+impl Cache {
+    pub fn new(budget: usize) -> Self {
+        let inner = CacheInner {
+            stats: CacheStats {
+                budget,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        Self {
+            inner: Mutex::new(inner),
+        }
+    }
+
+    pub fn get(&self, key: &CacheKey) -> Option<CacheValue> {
+        let mut inner = self.lock();
+        let inner = &mut *inner;
+        inner.tick += 1;
+
+        let Some(entry) = inner.values.get_mut(key) else {
+            inner.stats.misses += 1;
+            return None;
+        };
+        inner.stats.hits += 1;
+        inner.recency.remove(&entry.last_used);
+        entry.last_used = inner.tick;
+        inner.recency.insert(entry.last_used, key.clone());
+        Some(entry.value.clone())
+    }
+
+    pub fn insert(&self, key: CacheKey, value: CacheValue) {
+        let size = value.size();
+        let mut inner = self.lock();
+        let inner = &mut *inner;
+        if size > inner.stats.budget {
+            return;
+        }
+
+        inner.tick += 1;
+        if let Some(previous) = inner.values.remove(&key) {
+            inner.recency.remove(&previous.last_used);
+            inner.stats.bytes -= previous.size;
+        }
+
+        while inner.stats.bytes + size > inner.stats.budget {
+            let Some((_, oldest)) = inner.recency.pop_first() else {
+                break;
+            };
+            if let Some(evicted) = inner.values.remove(&oldest) {
+                inner.stats.bytes -= evicted.size;
+                inner.stats.evictions += 1;
+            }
+        }
+
+        inner.recency.insert(inner.tick, key.clone());
+        inner.values.insert(
+            key,
+            CacheEntry {
+                value,
+                size,
+                last_used: inner.tick,
+            },
+        );
+        inner.stats.bytes += size;
+    }
+
+    /// Drops every value, keeping the statistics.
+    pub fn clear(&self) {
+        let mut inner = self.lock();
+        inner.values.clear();
+        inner.recency.clear();
+        inner.stats.bytes = 0;
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let inner = self.lock();
+        CacheStats {
+            entries: inner.values.len(),
+            ..inner.stats
+        }
+    }
+
+    // Values are only ever inserted whole, so the cache is still consistent after a panic
+    fn lock(&self) -> MutexGuard<'_, CacheInner> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+//////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_key::FileKey;
+
+    /// A file of `length` bytes, whose cached size only depends on the length & `name`'s.
+    fn file(name: &str, length: usize) -> (CacheKey, CacheValue) {
+        let path = format!("exd/{}.bin", name);
+        let origin = FileOrigin::SqPack(FileKey::new(&path).unwrap());
+        let file = FfxivFile::new(path.clone(), vec![0; length].into(), false);
+        (CacheKey::File(path), CacheValue::File(file, origin))
+    }
+
+    fn contains(cache: &Cache, name: &str) -> bool {
+        cache.get(&file(name, 0).0).is_some()
+    }
+
+    #[test]
+    fn hits_and_misses() {
+        let (key, value) = file("a", 100);
+        let size = value.size();
+        let cache = Cache::new(1000);
+        assert!(cache.get(&key).is_none());
+        cache.insert(key.clone(), value);
+        let Some(CacheValue::File(cached, _)) = cache.get(&key) else {
+            panic!("expected a cached file");
+        };
+        assert_eq!(cached.len(), 100);
+
+        let stats = cache.stats();
+        assert_eq!(
+            stats,
+            CacheStats {
+                hits: 1,
+                misses: 1,
+                evictions: 0,
+                entries: 1,
+                bytes: size,
+                budget: 1000,
+            }
+        );
+        assert_eq!(stats.hit_ratio(), 0.5);
+        assert_eq!(CacheStats::default().hit_ratio(), 0.0);
+    }
+
+    #[test]
+    fn evicts_least_recently_used_within_budget() {
+        let size = file("a", 100).1.size();
+        let cache = Cache::new(3 * size);
+        for name in ["a", "b", "c"] {
+            let (key, value) = file(name, 100);
+            cache.insert(key, value);
+        }
+        // Using `a` makes `b` the oldest
+        assert!(contains(&cache, "a"));
+
+        let (key, value) = file("d", 100);
+        cache.insert(key, value);
+        assert!(!contains(&cache, "b"));
+        for name in ["a", "c", "d"] {
+            assert!(contains(&cache, name), "{}", name);
+        }
+
+        let stats = cache.stats();
+        assert_eq!(stats.evictions, 1);
+        assert_eq!(stats.entries, 3);
+        assert_eq!(stats.bytes, 3 * size);
+
+        // A value taking two slots evicts the two oldest
+        let (key, value) = file("e", 100 + size);
+        cache.insert(key, value);
+        let stats = cache.stats();
+        assert_eq!((stats.evictions, stats.entries), (3, 2));
+        assert!(stats.bytes <= stats.budget);
+        assert!(contains(&cache, "d") && contains(&cache, "e"));
+    }
+
+    #[test]
+    fn skips_values_over_budget() {
+        let (key, value) = file("a", 100);
+        let cache = Cache::new(value.size() - 1);
+        cache.insert(key.clone(), value);
+        assert!(cache.get(&key).is_none());
+        assert_eq!(cache.stats().entries, 0);
+        assert_eq!(cache.stats().bytes, 0);
+    }
+
+    #[test]
+    fn replacing_and_clearing() {
+        let cache = Cache::new(10_000);
+        let (key, value) = file("a", 100);
+        cache.insert(key.clone(), value);
+        let (_, value) = file("a", 200);
+        let size = value.size();
+        cache.insert(key.clone(), value);
+        assert_eq!(cache.stats().entries, 1);
+        assert_eq!(cache.stats().bytes, size);
+
+        assert!(cache.get(&key).is_some());
+        cache.clear();
+        let stats = cache.stats();
+        assert_eq!((stats.entries, stats.bytes), (0, 0));
+        assert_eq!(stats.hits, 1);
+        assert!(cache.get(&key).is_none());
+    }
+}
@@ -0,0 +1,135 @@
+use std::path::{Path, PathBuf};
+
+use crate::{ffxiv_library::FfxivLibrary, sqpack::PlatformId, version::GameVersion};
+
+//////////////////////////////////////////
+
+/// Where the game installs itself, relative to a Wine prefix.
+static PREFIX_GAME_PATHS: &[&str] = &[
+    "drive_c/Program Files (x86)/SquareEnix/FINAL FANTASY XIV - A Realm Reborn/game",
+    "drive_c/Program Files/SquareEnix/FINAL FANTASY XIV - A Realm Reborn/game",
+];
+
+/// Steam installs, relative to the home directory.
+static STEAM_ROOTS: &[&str] = &[
+    ".steam/steam",
+    ".local/share/Steam",
+    ".var/app/com.valvesoftware.Steam/.local/share/Steam",
+];
+
+static STEAM_GAME_PATH: &str = "steamapps/common/FINAL FANTASY XIV Online/game";
+
+/// Game directories, relative to the home directory.
+static HOME_GAME_PATHS: &[&str] = &[
+    // XIVLauncher.Core's default game path
+    ".xlcore/ffxiv/game",
+    "FINAL FANTASY XIV - A Realm Reborn/game",
+    "FINAL FANTASY XIV Online/game",
+];
+
+/// Wine prefixes, relative to the home directory.
+static HOME_PREFIXES: &[&str] = &[".xlcore/wineprefix", ".wine"];
+
+//////////////////////////////////////////
+
+#[derive(Debug, Clone)]
+pub struct GameInstallation {
+    /// The `game` directory, containing `ffxivgame.ver` & `sqpack`.
+    pub game_path: PathBuf,
+    pub version: GameVersion,
+    pub platform: PlatformId,
+}
+
+impl GameInstallation {
+    /// Checks whether a directory holds a game installation. Only the directory structure is
+    /// checked: `ffxivgame.ver` must parse, and `sqpack/ffxiv/0a0000.{platform}.index` must exist.
+    pub fn from_game_directory(game_path: impl AsRef<Path>) -> Option<Self> {
+        let game_path = game_path.as_ref();
+        let version = GameVersion::from_file(game_path.join("ffxivgame.ver")).ok()?;
+
+        let repository_path = game_path.join("sqpack").join("ffxiv");
+        let platform = PlatformId::ALL.into_iter().find(|platform| {
+            repository_path
+                .join(format!("0a0000.{}.index", platform.file_extension()))
+                .is_file()
+        })?;
+
+        Some(Self {
+            game_path: game_path.to_path_buf(),
+            version,
+            platform,
+        })
+    }
+
+    /// Finds every installation in the usual Linux locations under `$HOME`.
+    pub fn discover() -> Vec<Self> {
+        match std::env::var_os("HOME") {
+            Some(home) => Self::discover_in(home),
+            None => Vec::new(),
+        }
+    }
+
+    /// Finds every installation in the usual Linux locations under the given home directory:
+    /// Steam (& its Proton prefixes), XIVLauncher.Core, Lutris & plain Wine prefixes.
+    pub fn discover_in(home: impl AsRef<Path>) -> Vec<Self> {
+        let home = home.as_ref();
+
+        let mut prefixes = HOME_PREFIXES
+            .iter()
+            .map(|prefix| home.join(prefix))
+            .collect::<Vec<_>>();
+        // Lutris creates a prefix per game, under ~/Games by default
+        prefixes.extend(subdirectories(home.join("Games")));
+
+        let mut candidates = HOME_GAME_PATHS
+            .iter()
+            .map(|game_path| home.join(game_path))
+            .collect::<Vec<_>>();
+        for steam_root in STEAM_ROOTS.iter().map(|root| home.join(root)) {
+            candidates.push(steam_root.join(STEAM_GAME_PATH));
+            let compat_data = steam_root.join("steamapps").join("compatdata");
+            prefixes.extend(subdirectories(compat_data).map(|app| app.join("pfx")));
+        }
+        for prefix in prefixes {
+            candidates.extend(
+                PREFIX_GAME_PATHS
+                    .iter()
+                    .map(|game_path| prefix.join(game_path)),
+            );
+        }
+
+        let mut installations = Vec::<Self>::new();
+        for candidate in candidates {
+            let Some(installation) = Self::from_game_directory(&candidate) else {
+                continue;
+            };
+
+            // The same install is often reachable through several paths, e.g. Steam's symlinks
+            let canonical = candidate.canonicalize().ok();
+            let duplicate = installations
+                .iter()
+                .any(|existing| existing.game_path.canonicalize().ok() == canonical);
+            if !duplicate {
+                installations.push(installation);
+            }
+        }
+        installations
+    }
+
+    pub fn sqpack_path(&self) -> PathBuf {
+        self.game_path.join("sqpack")
+    }
+
+    pub fn library(&self) -> FfxivLibrary {
+        FfxivLibrary::with_platform(self.sqpack_path(), self.platform)
+    }
+}
+
+fn subdirectories(path: PathBuf) -> impl Iterator<Item = PathBuf> {
+    std::fs::read_dir(path)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|dir_entry| dir_entry.path())
+        .filter(|path| path.is_dir())
+}
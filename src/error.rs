@@ -0,0 +1,131 @@
+use std::{fmt::Display, string::FromUtf8Error};
+
+//////////////////////////////////////////
+
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    InvalidMagic {
+        kind: &'static str,
+        path: Option<String>,
+    },
+    UnknownValue {
+        kind: &'static str,
+        value: String,
+        path: Option<String>,
+    },
+    Truncated {
+        path: Option<String>,
+    },
+    InvalidChecksum {
+        kind: &'static str,
+        path: Option<String>,
+    },
+    InvalidString(FromUtf8Error),
+    InvalidVersion(String),
+    PathNotFound(String),
+    /// Writing would need more `.datN` files, or a larger offset, than an index can refer to.
+    ArchiveFull(String),
+    UnsupportedFileType {
+        file_type: u32,
+        path: String,
+    },
+    /// The operation can't be done with the library's source, e.g. writing to an archive.
+    Unsupported(String),
+}
+
+impl Error {
+    pub(crate) fn unknown_value(kind: &'static str, value: impl Display) -> Self {
+        Error::UnknownValue {
+            kind,
+            value: value.to_string(),
+            path: None,
+        }
+    }
+
+    /// Attaches the path of the file being parsed, if the error doesn't already carry one.
+    pub fn with_path(mut self, file_path: impl AsRef<str>) -> Self {
+        match &mut self {
+            Error::InvalidMagic { path, .. }
+            | Error::UnknownValue { path, .. }
+            | Error::Truncated { path }
+            | Error::InvalidChecksum { path, .. }
+                if path.is_none() =>
+            {
+                *path = Some(file_path.as_ref().to_string());
+            }
+            _ => {}
+        }
+        self
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::InvalidString(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "I/O error: {}", e)?,
+            Error::InvalidMagic { kind, .. } => write!(f, "Invalid magic for {}", kind)?,
+            Error::UnknownValue { kind, value, .. } => {
+                write!(f, "Unknown value for {}: {}", kind, value)?
+            }
+            Error::Truncated { .. } => write!(f, "Unexpected end of data")?,
+            Error::InvalidChecksum { kind, .. } => write!(f, "Invalid checksum for {}", kind)?,
+            Error::InvalidString(e) => write!(f, "Invalid string: {}", e)?,
+            Error::InvalidVersion(version) => write!(f, "Invalid version: {}", version)?,
+            Error::PathNotFound(path) => return write!(f, "Path not found: {}", path),
+            Error::ArchiveFull(name) => return write!(f, "SqPack archive is full: {}", name),
+            Error::UnsupportedFileType { file_type, path } => {
+                return write!(f, "Unsupported file type {} for {}", file_type, path)
+            }
+            Error::Unsupported(operation) => return write!(f, "Unsupported: {}", operation),
+        }
+
+        match self {
+            Error::InvalidMagic {
+                path: Some(path), ..
+            }
+            | Error::UnknownValue {
+                path: Some(path), ..
+            }
+            | Error::Truncated { path: Some(path) }
+            | Error::InvalidChecksum {
+                path: Some(path), ..
+            } => write!(f, " ({})", path),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(value: std::io::Error) -> Self {
+        match value.kind() {
+            std::io::ErrorKind::UnexpectedEof => Error::Truncated { path: None },
+            _ => Error::Io(value),
+        }
+    }
+}
+
+impl From<FromUtf8Error> for Error {
+    fn from(value: FromUtf8Error) -> Self {
+        Error::InvalidString(value)
+    }
+}
+
+impl From<Error> for std::io::Error {
+    fn from(value: Error) -> Self {
+        match value {
+            Error::Io(e) => e,
+            e => std::io::Error::new(std::io::ErrorKind::InvalidData, e),
+        }
+    }
+}
@@ -1,8 +1,8 @@
-use std::{error::Error, fmt::Display, io::Seek, ops::Deref};
+use std::{io::Seek, ops::Deref};
 
 use byteorder::{BigEndian, ReadBytesExt};
 
-use crate::ffxiv_file::FfxivFile;
+use crate::{error::Error, ffxiv_file::FfxivFile};
 
 use super::{ExcelColumn, ExcelColumnDataType, ExcelHeaderFile};
 
@@ -11,22 +11,19 @@ use super::{ExcelColumn, ExcelColumnDataType, ExcelHeaderFile};
 pub struct ExcelDataFile(Vec<ExcelDataRow>);
 
 impl ExcelDataFile {
-    pub fn from_file(
-        file: FfxivFile,
-        excel_file: &ExcelHeaderFile,
-    ) -> Result<Self, Box<dyn Error>> {
+    pub fn from_file(file: FfxivFile, excel_file: &ExcelHeaderFile) -> Result<Self, Error> {
         let mut reader = std::io::BufReader::new(std::io::Cursor::new(&file[..]));
-        Self::from_reader(&mut reader, excel_file)
+        Self::from_reader(&mut reader, excel_file).map_err(|e| e.with_path(file.file_name()))
     }
 
     pub fn from_reader<R: ReadBytesExt + Seek>(
         reader: &mut R,
         excel_file: &ExcelHeaderFile,
-    ) -> Result<Self, Box<dyn Error>> {
+    ) -> Result<Self, Error> {
         let data_header = ExcelDataHeader::from_reader(reader)?;
         let row_infos = (0..data_header.num_rows)
-            .flat_map(|_| ExcelRowInfo::from_reader(reader))
-            .collect::<Vec<_>>();
+            .map(|_| ExcelRowInfo::from_reader(reader))
+            .collect::<Result<Vec<_>, _>>()?;
 
         let data_offset = excel_file.header.data_offset as u64;
         let column_data = &excel_file.columns;
@@ -49,12 +46,20 @@ impl ExcelDataFile {
     }
 }
 
+impl Deref for ExcelDataFile {
+    type Target = [ExcelDataRow];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
 //////////////////////////////////////////
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ExcelDataRow(Vec<ExcelDataType>, ExcelRowInfo);
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ExcelDataType {
     String(String),
     U64(u64),
@@ -68,16 +73,14 @@ impl ExcelDataRow {
         row_info: ExcelRowInfo,
         column_data: &[ExcelColumn],
         data_offset: u64,
-    ) -> Result<Self, Box<dyn Error>> {
+    ) -> Result<Self, Error> {
         let row_data_start = row_info.offset as u64 + 6;
         let row_data_end = row_data_start + data_offset;
 
         let data = column_data
             .iter()
-            .flat_map(|excel_column| {
-                read_cell_data(reader, row_data_start, row_data_end, excel_column)
-            })
-            .collect::<Vec<_>>();
+            .map(|excel_column| read_cell_data(reader, row_data_start, row_data_end, excel_column))
+            .collect::<Result<Vec<_>, _>>()?;
 
         Ok(Self(data, row_info))
     }
@@ -85,6 +88,20 @@ impl ExcelDataRow {
     pub fn row_info(&self) -> &ExcelRowInfo {
         &self.1
     }
+
+    /// The row as a CSV record: its id, then every column, with strings quoted.
+    pub fn to_csv(&self) -> String {
+        [self.row_info().row_id.to_string()]
+            .into_iter()
+            .chain(self.iter().map(|entry| match entry {
+                ExcelDataType::String(v) => format!("\"{}\"", v.replace('"', "\"\"")),
+                ExcelDataType::I64(v) => format!("{}", v),
+                ExcelDataType::U64(v) => format!("{}", v),
+                ExcelDataType::F32(v) => format!("{}", v),
+            }))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
 }
 
 fn read_cell_data(
@@ -92,7 +109,7 @@ fn read_cell_data(
     row_data_start: u64,
     row_data_end: u64,
     excel_column: &ExcelColumn,
-) -> Result<ExcelDataType, Box<dyn Error>> {
+) -> Result<ExcelDataType, Error> {
     let start_offset = row_data_start + excel_column.offset as u64;
     reader.seek(std::io::SeekFrom::Start(start_offset))?;
     Ok(match excel_column.data_type {
@@ -150,14 +167,14 @@ impl Deref for ExcelDataRow {
 
 //////////////////////////////////////////
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ExcelRowInfo {
     pub row_id: u32,
     pub offset: u32,
 }
 
 impl ExcelRowInfo {
-    pub fn from_reader(reader: &mut impl ReadBytesExt) -> Result<Self, Box<dyn Error>> {
+    pub fn from_reader(reader: &mut impl ReadBytesExt) -> Result<Self, Error> {
         let row_id = reader.read_u32::<BigEndian>()?;
         let offset = reader.read_u32::<BigEndian>()?;
         Ok(Self { row_id, offset })
@@ -170,40 +187,24 @@ struct ExcelDataHeader {
     num_rows: u32,
 }
 
-#[derive(Debug)]
-pub enum ExcelHeaderError {
-    InvalidMagic,
-}
-
 impl ExcelDataHeader {
-    pub fn from_reader(reader: &mut impl ReadBytesExt) -> Result<Self, Box<dyn Error>> {
+    pub fn from_reader(reader: &mut impl ReadBytesExt) -> Result<Self, Error> {
         let magic = reader.read_u32::<BigEndian>()?;
         if magic != 0x45584446 {
-            return Err(Box::new(ExcelHeaderError::InvalidMagic));
+            return Err(Error::InvalidMagic {
+                kind: "ExcelData",
+                path: None,
+            });
         }
         let _version = reader.read_u16::<BigEndian>()?;
         let _u1 = reader.read_u16::<BigEndian>()?;
         let row_info_size = reader.read_u32::<BigEndian>()?;
         let _u2 = (0..10)
-            .flat_map(|_| reader.read_u16::<BigEndian>())
-            .collect::<Vec<_>>();
+            .map(|_| reader.read_u16::<BigEndian>())
+            .collect::<Result<Vec<_>, _>>()?;
 
         let num_rows = row_info_size / 8;
 
         Ok(Self { num_rows })
     }
 }
-
-impl Error for ExcelHeaderError {}
-
-impl Display for ExcelHeaderError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            match *self {
-                ExcelHeaderError::InvalidMagic => "InvalidMagic",
-            }
-        )
-    }
-}
@@ -1,8 +1,6 @@
-use std::{error::Error, fmt::Display};
-
 use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
 
-use crate::ffxiv_file::FfxivFile;
+use crate::{error::Error, ffxiv_file::FfxivFile};
 
 //////////////////////////////////////////
 
@@ -15,22 +13,22 @@ pub struct ExcelHeaderFile {
 }
 
 impl ExcelHeaderFile {
-    pub fn from_file(file: FfxivFile) -> Result<Self, Box<dyn Error>> {
+    pub fn from_file(file: FfxivFile) -> Result<Self, Error> {
         let mut reader = std::io::BufReader::new(std::io::Cursor::new(&file[..]));
-        Self::from_reader(&mut reader)
+        Self::from_reader(&mut reader).map_err(|e| e.with_path(file.file_name()))
     }
 
-    pub fn from_reader(reader: &mut impl ReadBytesExt) -> Result<Self, Box<dyn Error>> {
+    pub fn from_reader(reader: &mut impl ReadBytesExt) -> Result<Self, Error> {
         let header = ExcelHeader::from_reader(reader)?;
         let columns = (0..header.column_count)
-            .flat_map(|_| ExcelColumn::from_reader(reader))
-            .collect::<Vec<_>>();
+            .map(|_| ExcelColumn::from_reader(reader))
+            .collect::<Result<Vec<_>, _>>()?;
         let pages = (0..header.page_count)
-            .flat_map(|_| ExcelPageInfo::from_reader(reader))
-            .collect::<Vec<_>>();
+            .map(|_| ExcelPageInfo::from_reader(reader))
+            .collect::<Result<Vec<_>, _>>()?;
         let languages = (0..header.language_count)
-            .flat_map(|_| ExcelLanguage::from_reader(reader))
-            .collect::<Vec<_>>();
+            .map(|_| ExcelLanguage::from_reader(reader))
+            .collect::<Result<Vec<_>, _>>()?;
 
         Ok(Self {
             header,
@@ -75,7 +73,7 @@ pub enum ExcelColumnDataType {
 }
 
 impl ExcelColumn {
-    pub fn from_reader(reader: &mut impl ReadBytesExt) -> Result<Self, Box<dyn Error>> {
+    pub fn from_reader(reader: &mut impl ReadBytesExt) -> Result<Self, Error> {
         let data_type = reader.read_u16::<BigEndian>()?;
         let offset = reader.read_u16::<BigEndian>()?;
 
@@ -99,7 +97,7 @@ impl ExcelColumn {
             0x1E => ExcelColumnDataType::PackedBool5,
             0x1F => ExcelColumnDataType::PackedBool6,
             0x20 => ExcelColumnDataType::PackedBool7,
-            _ => panic!("Unexpected data type: {}", data_type),
+            _ => return Err(Error::unknown_value("ExcelColumnDataType", data_type)),
         };
 
         Ok(Self { data_type, offset })
@@ -125,16 +123,14 @@ pub enum ExcelVariant {
     SubRows,
 }
 
-#[derive(Debug)]
-pub enum ExcelHeaderError {
-    InvalidMagic,
-}
-
 impl ExcelHeader {
-    pub fn from_reader(reader: &mut impl ReadBytesExt) -> Result<Self, Box<dyn Error>> {
+    pub fn from_reader(reader: &mut impl ReadBytesExt) -> Result<Self, Error> {
         let magic = reader.read_u32::<BigEndian>()?;
         if magic != 0x45584846 {
-            return Err(Box::new(ExcelHeaderError::InvalidMagic));
+            return Err(Error::InvalidMagic {
+                kind: "ExcelHeader",
+                path: None,
+            });
         }
         let _ = reader.read_u16::<BigEndian>()?;
         let data_offset = reader.read_u16::<BigEndian>()?;
@@ -154,7 +150,7 @@ impl ExcelHeader {
         let variant = match variant {
             1 => ExcelVariant::Default,
             2 => ExcelVariant::SubRows,
-            _ => panic!("Invalid variant type: {}", variant),
+            _ => return Err(Error::unknown_value("ExcelVariant", variant)),
         };
 
         Ok(Self {
@@ -168,23 +164,9 @@ impl ExcelHeader {
     }
 }
 
-impl Error for ExcelHeaderError {}
-
-impl Display for ExcelHeaderError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            match *self {
-                ExcelHeaderError::InvalidMagic => "InvalidMagic",
-            }
-        )
-    }
-}
-
 //////////////////////////////////////////
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ExcelLanguage {
     None,
     Japanese,
@@ -197,7 +179,7 @@ pub enum ExcelLanguage {
 }
 
 impl ExcelLanguage {
-    pub fn from_reader(reader: &mut impl ReadBytesExt) -> Result<Self, Box<dyn Error>> {
+    pub fn from_reader(reader: &mut impl ReadBytesExt) -> Result<Self, Error> {
         let language = reader.read_u16::<LittleEndian>()?;
 
         Ok(match language {
@@ -209,7 +191,7 @@ impl ExcelLanguage {
             5 => ExcelLanguage::ChineseSimplified,
             6 => ExcelLanguage::ChineseTraditional,
             7 => ExcelLanguage::Korean,
-            _ => panic!("Unexpected language value: {}", language),
+            _ => return Err(Error::unknown_value("ExcelLanguage", language)),
         })
     }
 
@@ -237,7 +219,7 @@ pub struct ExcelPageInfo {
 }
 
 impl ExcelPageInfo {
-    pub fn from_reader(reader: &mut impl ReadBytesExt) -> Result<Self, Box<dyn Error>> {
+    pub fn from_reader(reader: &mut impl ReadBytesExt) -> Result<Self, Error> {
         let start_row_id = reader.read_u32::<BigEndian>()?;
         let row_count = reader.read_u32::<BigEndian>()?;
 
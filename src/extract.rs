@@ -0,0 +1,52 @@
+use std::path::Path;
+
+use crate::{
+    error::Error, ffxiv_file::FfxivFile, file_key::FileKey, sqpack::SqPackIndexTableEntry,
+};
+
+//////////////////////////////////////////
+
+/// The outcome of `FfxivLibrary::extract_to_directory`.
+#[derive(Debug, Default)]
+pub struct ExtractReport {
+    pub files_written: usize,
+    pub bytes_written: u64,
+    /// Every path which couldn't be read or written, with the reason.
+    pub failures: Vec<(String, Error)>,
+}
+
+impl ExtractReport {
+    pub fn is_ok(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// Writes a file below `output_path`, or records why it couldn't be.
+    pub fn write(&mut self, output_path: &Path, path: &str, file: Result<FfxivFile, Error>) {
+        let result = file.and_then(|file| {
+            file.write(output_path)?;
+            Ok(file.len() as u64)
+        });
+
+        match result {
+            Ok(size) => {
+                self.files_written += 1;
+                self.bytes_written += size;
+            }
+            Err(e) => self.failures.push((path.to_string(), e)),
+        }
+    }
+}
+
+//////////////////////////////////////////
+
+/// A file to extract, along with where its data is.
+pub(crate) struct ExtractJob {
+    pub path: String,
+    pub file_key: FileKey,
+    pub entry: SqPackIndexTableEntry,
+}
+
+/// Orders jobs so that each dat file is read from start to end.
+pub(crate) fn sort_jobs(jobs: &mut [ExtractJob]) {
+    jobs.sort_by_key(|job| (job.file_key, job.entry.data_file_id, job.entry.offset));
+}
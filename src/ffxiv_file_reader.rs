@@ -0,0 +1,866 @@
+use std::{
+    io::{Cursor, Read, Seek, SeekFrom},
+    ops::Deref,
+};
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt, WriteBytesExt};
+use flate2::bufread::DeflateDecoder;
+use rayon::prelude::*;
+
+use crate::{error::Error, ffxiv_file::FfxivFile, sqpack::SqPackIndexTableEntry};
+
+//////////////////////////////////////////
+
+/// Sizes are read from the `.dat` file, so buffers grow as data arrives past this much.
+const MAX_PREALLOCATION: u64 = 0x100_0000;
+
+/// Loose files are read in pieces of this size, rather than all at once.
+const LOOSE_SEGMENT_SIZE: u64 = 0x10000;
+
+//////////////////////////////////////////
+
+/// A `Read + Seek` view over a single SqPack entry, which only decompresses the block
+/// holding the current position.
+pub struct FfxivFileReader<R> {
+    reader: R,
+    path: String,
+    segments: Vec<Segment>,
+    size: u64,
+    declared_size: u32,
+    position: u64,
+    placeholder: bool,
+    big_endian: bool,
+    current: Option<(usize, Box<[u8]>)>,
+}
+
+impl<R: Read + Seek> FfxivFileReader<R> {
+    pub fn new(
+        mut reader: R,
+        path: impl AsRef<str>,
+        entry: &SqPackIndexTableEntry,
+    ) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let layout = FileLayout::from_reader(&mut reader, entry).map_err(|e| e.with_path(path))?;
+
+        Ok(Self {
+            reader,
+            path: path.to_string(),
+            segments: layout.segments,
+            size: layout.size,
+            declared_size: layout.declared_size,
+            position: 0,
+            placeholder: layout.placeholder,
+            big_endian: entry.platform.is_big_endian(),
+            current: None,
+        })
+    }
+
+    /// A reader over a file which isn't in a SqPack, e.g. a loose file from an overlay.
+    pub(crate) fn from_loose(reader: R, path: impl AsRef<str>, size: u64) -> Self {
+        let mut layout = FileLayout {
+            segments: Vec::new(),
+            size: 0,
+            declared_size: u32::try_from(size).unwrap_or(u32::MAX),
+            placeholder: false,
+        };
+        let mut offset = 0;
+        while offset < size {
+            let segment_size = (size - offset).min(LOOSE_SEGMENT_SIZE);
+            layout.push(SegmentSource::Raw(offset), segment_size as u32);
+            offset += segment_size;
+        }
+
+        Self {
+            reader,
+            path: path.as_ref().to_string(),
+            segments: layout.segments,
+            size: layout.size,
+            declared_size: layout.declared_size,
+            position: 0,
+            placeholder: false,
+            big_endian: false,
+            current: None,
+        }
+    }
+
+    pub fn file_name(&self) -> &str {
+        &self.path
+    }
+
+    /// Size of the decompressed file.
+    pub fn len(&self) -> u64 {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Size of the decompressed file, as recorded in the entry's header.
+    pub fn declared_len(&self) -> u64 {
+        self.declared_size as u64
+    }
+
+    /// Whether this reader is over an empty (placeholder) SqPack entry.
+    pub fn is_placeholder(&self) -> bool {
+        self.placeholder
+    }
+
+    /// Decompresses the whole entry into an `FfxivFile`.
+    pub fn into_file(mut self) -> Result<FfxivFile, Error> {
+        let mut file_contents = Vec::with_capacity(self.size.min(MAX_PREALLOCATION) as usize);
+        for index in 0..self.segments.len() {
+            self.load_segment(index)
+                .map_err(|e| e.with_path(&self.path))?;
+            file_contents.extend_from_slice(self.current_data());
+        }
+
+        Ok(FfxivFile::new(
+            self.path,
+            file_contents.into(),
+            self.placeholder,
+        ))
+    }
+
+    fn load_segment(&mut self, index: usize) -> Result<(), Error> {
+        if !matches!(self.current, Some((current, _)) if current == index) {
+            let data = self.segments[index].read(&mut self.reader, self.big_endian)?;
+            self.current = Some((index, data));
+        }
+
+        Ok(())
+    }
+
+    fn current_data(&self) -> &[u8] {
+        self.current.as_ref().map_or(&[], |(_, data)| &data[..])
+    }
+}
+
+impl<R: Read + Seek + Clone + Send + Sync> FfxivFileReader<R> {
+    /// Decompresses the whole entry into an `FfxivFile`, spreading its blocks over rayon's
+    /// thread pool. Each thread reads through its own clone of the reader, so this is meant
+    /// for readers which share a handle, such as `PositionalReader`.
+    pub fn into_file_parallel(self) -> Result<FfxivFile, Error> {
+        let big_endian = self.big_endian;
+        let segments = self
+            .segments
+            .par_iter()
+            .map_init(
+                || self.reader.clone(),
+                |reader, segment| segment.read(reader, big_endian),
+            )
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.with_path(&self.path))?;
+
+        let mut file_contents = Vec::with_capacity(self.size.min(MAX_PREALLOCATION) as usize);
+        for data in segments {
+            file_contents.extend_from_slice(&data);
+        }
+        Ok(FfxivFile::new(
+            self.path,
+            file_contents.into(),
+            self.placeholder,
+        ))
+    }
+}
+
+impl<R: Read + Seek> Read for FfxivFileReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.position >= self.size || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let position = self.position;
+        let index = self
+            .segments
+            .partition_point(|segment| segment.end() <= position);
+        let segment_offset = (position - self.segments[index].start) as usize;
+
+        self.load_segment(index)
+            .map_err(|e| e.with_path(&self.path))?;
+        let available = &self.current_data()[segment_offset..];
+        let count = available.len().min(buf.len());
+        buf[..count].copy_from_slice(&available[..count]);
+
+        self.position += count as u64;
+        Ok(count)
+    }
+}
+
+impl<R> Seek for FfxivFileReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.size.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+
+        self.position = position.ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(self.position)
+    }
+}
+
+//////////////////////////////////////////
+
+/// Decompresses a whole entry from the bytes of a memory-mapped `.dat`. Blocks are inflated
+/// from slices borrowed straight from the mapping into the file's buffer, in parallel if asked.
+pub(crate) fn read_mapped_file(
+    data: &[u8],
+    path: &str,
+    entry: &SqPackIndexTableEntry,
+    parallel: bool,
+) -> Result<FfxivFile, Error> {
+    let layout =
+        FileLayout::from_reader(&mut Cursor::new(data), entry).map_err(|e| e.with_path(path))?;
+    let big_endian = entry.platform.is_big_endian();
+
+    // The size comes from the .dat file, so larger files are decompressed a segment at a time
+    // rather than into a buffer allocated up front
+    if layout.size > MAX_PREALLOCATION {
+        let reader = FfxivFileReader::new(Cursor::new(data), path, entry)?;
+        return if parallel {
+            reader.into_file_parallel()
+        } else {
+            reader.into_file()
+        };
+    }
+
+    // Segments are contiguous, so each one gets its own part of the buffer
+    let mut file_contents = vec![0; layout.size as usize];
+    let mut outputs = Vec::with_capacity(layout.segments.len());
+    let mut rest = &mut file_contents[..];
+    for segment in &layout.segments {
+        let (output, remainder) = rest.split_at_mut(segment.size as usize);
+        outputs.push((segment, output));
+        rest = remainder;
+    }
+
+    let read =
+        |(segment, output): (&Segment, &mut [u8])| segment.read_mapped(data, output, big_endian);
+    if parallel {
+        outputs.into_par_iter().try_for_each(read)
+    } else {
+        outputs.into_iter().try_for_each(read)
+    }
+    .map_err(|e| e.with_path(path))?;
+
+    Ok(FfxivFile::new(
+        path.to_string(),
+        file_contents.into(),
+        layout.placeholder,
+    ))
+}
+
+//////////////////////////////////////////
+
+/// A contiguous range of the decompressed file, and where its bytes come from.
+struct Segment {
+    pub start: u64,
+    pub size: u32,
+    pub source: SegmentSource,
+}
+
+enum SegmentSource {
+    /// Bytes which don't exist in the .dat file, such as a rebuilt model header.
+    Inline(Box<[u8]>),
+    /// Uncompressed bytes at the given .dat offset.
+    Raw(u64),
+    /// A block (header + data) at the given .dat offset.
+    Block(u64),
+}
+
+impl Segment {
+    fn end(&self) -> u64 {
+        self.start + self.size as u64
+    }
+
+    /// Reads & decompresses the segment's bytes.
+    fn read(&self, reader: &mut (impl Read + Seek), big_endian: bool) -> Result<Box<[u8]>, Error> {
+        let data = match &self.source {
+            SegmentSource::Inline(data) => data.clone(),
+            SegmentSource::Raw(offset) => {
+                reader.seek(SeekFrom::Start(*offset))?;
+                let mut data = Vec::new();
+                reader.take(self.size as u64).read_to_end(&mut data)?;
+                data.into_boxed_slice()
+            }
+            SegmentSource::Block(offset) => {
+                let mut data =
+                    Vec::with_capacity((self.size as u64).min(MAX_PREALLOCATION) as usize);
+                if big_endian {
+                    read_block::<BigEndian>(reader, *offset, &mut data)?;
+                } else {
+                    read_block::<LittleEndian>(reader, *offset, &mut data)?;
+                }
+                data.into_boxed_slice()
+            }
+        };
+
+        if data.len() != self.size as usize {
+            return Err(Error::Truncated { path: None });
+        }
+        Ok(data)
+    }
+
+    /// Decompresses the segment's bytes from a mapped `.dat` into `output`, which must be
+    /// exactly the segment's size.
+    fn read_mapped(&self, data: &[u8], output: &mut [u8], big_endian: bool) -> Result<(), Error> {
+        let truncated = || Error::Truncated { path: None };
+        match &self.source {
+            SegmentSource::Inline(inline) if inline.len() == output.len() => {
+                output.copy_from_slice(inline);
+            }
+            SegmentSource::Inline(_) => return Err(truncated()),
+            SegmentSource::Raw(offset) => {
+                let raw = usize::try_from(*offset)
+                    .ok()
+                    .and_then(|start| data.get(start..start.checked_add(output.len())?))
+                    .ok_or_else(truncated)?;
+                output.copy_from_slice(raw);
+            }
+            SegmentSource::Block(offset) => {
+                let (block_header, block_data) = if big_endian {
+                    borrow_block::<BigEndian>(data, *offset)?
+                } else {
+                    borrow_block::<LittleEndian>(data, *offset)?
+                };
+
+                if block_header.is_uncompressed() {
+                    if block_data.len() != output.len() {
+                        return Err(truncated());
+                    }
+                    output.copy_from_slice(block_data);
+                } else {
+                    let mut decoder = DeflateDecoder::new(block_data);
+                    decoder.read_exact(output)?;
+                    if decoder.read(&mut [0])? != 0 {
+                        return Err(truncated());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+//////////////////////////////////////////
+
+struct FileLayout {
+    pub segments: Vec<Segment>,
+    pub size: u64,
+    pub declared_size: u32,
+    pub placeholder: bool,
+}
+
+impl FileLayout {
+    pub fn from_reader(
+        reader: &mut (impl ReadBytesExt + Seek),
+        entry: &SqPackIndexTableEntry,
+    ) -> Result<Self, Error> {
+        if entry.platform.is_big_endian() {
+            Self::from_reader_as::<BigEndian>(reader, entry)
+        } else {
+            Self::from_reader_as::<LittleEndian>(reader, entry)
+        }
+    }
+
+    fn from_reader_as<E: ByteOrder>(
+        reader: &mut (impl ReadBytesExt + Seek),
+        entry: &SqPackIndexTableEntry,
+    ) -> Result<Self, Error> {
+        reader.seek(SeekFrom::Start(entry.offset as u64))?;
+
+        let header = CommonHeader::from_reader::<E>(reader)?;
+        let blocks_offset = entry.offset as u64 + header.size as u64;
+
+        // The header size comes from the .dat file, so the blocks must be checked to be in it
+        let position = reader.stream_position()?;
+        if blocks_offset > reader.seek(SeekFrom::End(0))? {
+            return Err(Error::Truncated { path: None });
+        }
+        reader.seek(SeekFrom::Start(position))?;
+
+        let mut layout = Self {
+            segments: Vec::new(),
+            size: 0,
+            declared_size: header.file_size,
+            placeholder: false,
+        };
+        match header.file_type {
+            // Empty entries reserve an index slot, but have no data behind them
+            FileType::Empty => layout.placeholder = true,
+            FileType::Standard => layout.read_standard::<E>(reader, &header, blocks_offset)?,
+            FileType::Texture => layout.read_texture::<E>(reader, &header, blocks_offset)?,
+            FileType::Model => layout.read_model::<E>(reader, &header, blocks_offset)?,
+        }
+
+        Ok(layout)
+    }
+
+    fn push(&mut self, source: SegmentSource, size: u32) {
+        if size == 0 {
+            return;
+        }
+        self.segments.push(Segment {
+            start: self.size,
+            size,
+            source,
+        });
+        self.size += size as u64;
+    }
+
+    fn push_block<E: ByteOrder>(
+        &mut self,
+        reader: &mut (impl ReadBytesExt + Seek),
+        offset: u64,
+    ) -> Result<u32, Error> {
+        reader.seek(SeekFrom::Start(offset))?;
+        let block_header = BlockHeader::from_reader::<E>(reader)?;
+        self.push(SegmentSource::Block(offset), block_header.uncompressed_size);
+        Ok(block_header.uncompressed_size)
+    }
+
+    fn read_standard<E: ByteOrder>(
+        &mut self,
+        reader: &mut (impl ReadBytesExt + Seek),
+        header: &CommonHeader,
+        blocks_offset: u64,
+    ) -> Result<(), Error> {
+        let block_info = (0..header.block_count)
+            .map(|_| BlockInfo::from_reader::<E>(reader))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for info in block_info {
+            self.push(
+                SegmentSource::Block(blocks_offset + info.offset as u64),
+                info.uncompressed_size as u32,
+            );
+        }
+
+        Ok(())
+    }
+
+    fn read_texture<E: ByteOrder>(
+        &mut self,
+        reader: &mut (impl ReadBytesExt + Seek),
+        header: &CommonHeader,
+        blocks_offset: u64,
+    ) -> Result<(), Error> {
+        // For textures, block_count is the number of LODs (mip levels)
+        let lod_blocks = (0..header.block_count)
+            .map(|_| LodBlockInfo::from_reader::<E>(reader))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Directly after the LOD table comes the size of every sub-block, across all LODs
+        let sub_block_count = lod_blocks
+            .iter()
+            .map(|lod| lod.block_offset.checked_add(lod.block_count))
+            .collect::<Option<Vec<_>>>()
+            .ok_or(Error::Truncated { path: None })?
+            .into_iter()
+            .max()
+            .unwrap_or(0);
+        let sub_block_sizes = (0..sub_block_count)
+            .map(|_| reader.read_u16::<E>())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // The uncompressed .tex header sits between the entry header & the first LOD's blocks
+        let tex_header_size = lod_blocks.first().map_or(0, |lod| lod.compressed_offset);
+        self.push(SegmentSource::Raw(blocks_offset), tex_header_size);
+
+        for lod in &lod_blocks {
+            let start = lod.block_offset as usize;
+            let end = start + lod.block_count as usize;
+
+            let mut offset = blocks_offset + lod.compressed_offset as u64;
+            for size in &sub_block_sizes[start..end] {
+                self.push_block::<E>(reader, offset)?;
+                offset += *size as u64;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read_model<E: ByteOrder>(
+        &mut self,
+        reader: &mut (impl ReadBytesExt + Seek),
+        header: &CommonHeader,
+        blocks_offset: u64,
+    ) -> Result<(), Error> {
+        let model_info = ModelBlockInfo::from_reader::<E>(reader)?;
+        let block_sizes = (0..model_info.block_counts.iter().map(|&n| n as u32).sum())
+            .map(|_| reader.read_u16::<E>())
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut block_sizes = block_sizes.into_iter();
+
+        // The model file header is rebuilt once every section's size is known
+        self.push(
+            SegmentSource::Inline(Box::new([])),
+            MODEL_HEADER_SIZE as u32,
+        );
+
+        let mut read_section = |layout: &mut Self, offset: u32, block_count: u16| {
+            let mut offset = blocks_offset + offset as u64;
+            let start = layout.size;
+            for _ in 0..block_count {
+                layout.push_block::<E>(reader, offset)?;
+                offset += block_sizes.next().unwrap_or(0) as u64;
+            }
+            Ok::<_, Error>((start as u32, (layout.size - start) as u32))
+        };
+
+        let counts = &model_info.block_counts;
+        let offsets = &model_info.offsets;
+        let (_, stack_size) = read_section(self, offsets.stack, counts.stack)?;
+        let (_, runtime_size) = read_section(self, offsets.runtime, counts.runtime)?;
+
+        let mut vertex_offsets = [0u32; 3];
+        let mut vertex_sizes = [0u32; 3];
+        let mut index_offsets = [0u32; 3];
+        let mut index_sizes = [0u32; 3];
+        for lod in 0..3 {
+            if counts.vertex_buffer[lod] != 0 {
+                let (start, size) =
+                    read_section(self, offsets.vertex_buffer[lod], counts.vertex_buffer[lod])?;
+                // LODs sharing the previous LOD's buffer are marked with a zero offset
+                if lod == 0 || start != vertex_offsets[lod - 1] {
+                    vertex_offsets[lod] = start;
+                }
+                vertex_sizes[lod] = size;
+            }
+
+            if counts.edge_geometry_vertex_buffer[lod] != 0 {
+                read_section(
+                    self,
+                    offsets.edge_geometry_vertex_buffer[lod],
+                    counts.edge_geometry_vertex_buffer[lod],
+                )?;
+            }
+
+            if counts.index_buffer[lod] != 0 {
+                let (start, size) =
+                    read_section(self, offsets.index_buffer[lod], counts.index_buffer[lod])?;
+                if lod == 0 || start != index_offsets[lod - 1] {
+                    index_offsets[lod] = start;
+                }
+                index_sizes[lod] = size;
+            }
+        }
+
+        let mut model_header = Vec::with_capacity(MODEL_HEADER_SIZE);
+        // For model entries, the final field of the common header is the model version
+        model_header.write_u32::<E>(header.block_count)?;
+        model_header.write_u32::<E>(stack_size)?;
+        model_header.write_u32::<E>(runtime_size)?;
+        model_header.write_u16::<E>(model_info.vertex_declaration_count)?;
+        model_header.write_u16::<E>(model_info.material_count)?;
+        for value in vertex_offsets
+            .into_iter()
+            .chain(index_offsets)
+            .chain(vertex_sizes)
+            .chain(index_sizes)
+        {
+            model_header.write_u32::<E>(value)?;
+        }
+        model_header.write_u8(model_info.lod_count)?;
+        model_header.write_u8(model_info.index_buffer_streaming_enabled as u8)?;
+        model_header.write_u8(model_info.edge_geometry_enabled as u8)?;
+        model_header.write_u8(0)?;
+
+        self.segments[0].source = SegmentSource::Inline(model_header.into_boxed_slice());
+        Ok(())
+    }
+}
+
+//////////////////////////////////////////
+
+fn read_block<E: ByteOrder>(
+    reader: &mut (impl ReadBytesExt + Seek),
+    offset: u64,
+    out: &mut Vec<u8>,
+) -> Result<(), Error> {
+    reader.seek(SeekFrom::Start(offset))?;
+    let block_header = BlockHeader::from_reader::<E>(reader)?;
+    let block_data = BlockData::from_reader(reader, &block_header)?;
+
+    if block_header.is_uncompressed() {
+        out.extend_from_slice(&block_data);
+    } else {
+        let mut decoder = DeflateDecoder::new(&block_data[..]);
+        decoder.read_to_end(out)?;
+    }
+    Ok(())
+}
+
+/// Like `read_block`, but borrows the block's stored bytes instead of copying them.
+fn borrow_block<E: ByteOrder>(data: &[u8], offset: u64) -> Result<(BlockHeader, &[u8]), Error> {
+    let mut reader = usize::try_from(offset)
+        .ok()
+        .and_then(|start| data.get(start..))
+        .ok_or(Error::Truncated { path: None })?;
+    let block_header = BlockHeader::from_reader::<E>(&mut reader)?;
+    let block_data = reader
+        .get(..block_header.data_size() as usize)
+        .ok_or(Error::Truncated { path: None })?;
+    Ok((block_header, block_data))
+}
+
+//////////////////////////////////////////
+
+struct BlockInfo {
+    pub offset: u32,
+    pub uncompressed_size: u16,
+}
+
+impl BlockInfo {
+    pub fn from_reader<E: ByteOrder>(reader: &mut impl ReadBytesExt) -> Result<Self, Error> {
+        let offset = reader.read_u32::<E>()?;
+        let _compressed_size = reader.read_u16::<E>()?;
+        let uncompressed_size = reader.read_u16::<E>()?;
+        Ok(Self {
+            offset,
+            uncompressed_size,
+        })
+    }
+}
+
+//////////////////////////////////////////
+
+#[allow(dead_code)]
+struct LodBlockInfo {
+    pub compressed_offset: u32,
+    pub compressed_size: u32,
+    pub decompressed_size: u32,
+    pub block_offset: u32,
+    pub block_count: u32,
+}
+
+impl LodBlockInfo {
+    pub fn from_reader<E: ByteOrder>(reader: &mut impl ReadBytesExt) -> Result<Self, Error> {
+        let compressed_offset = reader.read_u32::<E>()?;
+        let compressed_size = reader.read_u32::<E>()?;
+        let decompressed_size = reader.read_u32::<E>()?;
+        let block_offset = reader.read_u32::<E>()?;
+        let block_count = reader.read_u32::<E>()?;
+
+        Ok(Self {
+            compressed_offset,
+            compressed_size,
+            decompressed_size,
+            block_offset,
+            block_count,
+        })
+    }
+}
+
+//////////////////////////////////////////
+
+const MODEL_HEADER_SIZE: usize = 0x44;
+
+/// One value for each section of a model entry, in the order they're stored.
+#[derive(Debug)]
+struct ModelSections<T> {
+    pub stack: T,
+    pub runtime: T,
+    pub vertex_buffer: [T; 3],
+    pub edge_geometry_vertex_buffer: [T; 3],
+    pub index_buffer: [T; 3],
+}
+
+impl<T: Copy> ModelSections<T> {
+    fn from_fn(mut read: impl FnMut() -> Result<T, Error>) -> Result<Self, Error> {
+        let stack = read()?;
+        let runtime = read()?;
+        let vertex_buffer = [read()?, read()?, read()?];
+        let edge_geometry_vertex_buffer = [read()?, read()?, read()?];
+        let index_buffer = [read()?, read()?, read()?];
+
+        Ok(Self {
+            stack,
+            runtime,
+            vertex_buffer,
+            edge_geometry_vertex_buffer,
+            index_buffer,
+        })
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &T> {
+        [&self.stack, &self.runtime]
+            .into_iter()
+            .chain(&self.vertex_buffer)
+            .chain(&self.edge_geometry_vertex_buffer)
+            .chain(&self.index_buffer)
+    }
+}
+
+#[allow(dead_code)]
+struct ModelBlockInfo {
+    pub decompressed_sizes: ModelSections<u32>,
+    pub compressed_sizes: ModelSections<u32>,
+    pub offsets: ModelSections<u32>,
+    pub block_indices: ModelSections<u16>,
+    pub block_counts: ModelSections<u16>,
+    pub vertex_declaration_count: u16,
+    pub material_count: u16,
+    pub lod_count: u8,
+    pub index_buffer_streaming_enabled: bool,
+    pub edge_geometry_enabled: bool,
+}
+
+impl ModelBlockInfo {
+    pub fn from_reader<E: ByteOrder>(reader: &mut impl ReadBytesExt) -> Result<Self, Error> {
+        let mut read_u32 = || Ok(reader.read_u32::<E>()?);
+        let decompressed_sizes = ModelSections::from_fn(&mut read_u32)?;
+        let compressed_sizes = ModelSections::from_fn(&mut read_u32)?;
+        let offsets = ModelSections::from_fn(&mut read_u32)?;
+
+        let mut read_u16 = || Ok(reader.read_u16::<E>()?);
+        let block_indices = ModelSections::from_fn(&mut read_u16)?;
+        let block_counts = ModelSections::from_fn(&mut read_u16)?;
+
+        let vertex_declaration_count = reader.read_u16::<E>()?;
+        let material_count = reader.read_u16::<E>()?;
+        let lod_count = reader.read_u8()?;
+        let index_buffer_streaming_enabled = reader.read_u8()? != 0;
+        let edge_geometry_enabled = reader.read_u8()? != 0;
+        let _padding = reader.read_u8()?;
+
+        Ok(Self {
+            decompressed_sizes,
+            compressed_sizes,
+            offsets,
+            block_indices,
+            block_counts,
+            vertex_declaration_count,
+            material_count,
+            lod_count,
+            index_buffer_streaming_enabled,
+            edge_geometry_enabled,
+        })
+    }
+}
+
+//////////////////////////////////////////
+
+/// Blocks stored without compression carry this value in place of their compressed size.
+const UNCOMPRESSED_BLOCK_SIZE: u32 = 32000;
+
+struct BlockHeader {
+    pub compressed_size: u32,
+    pub uncompressed_size: u32,
+}
+
+impl BlockHeader {
+    pub fn from_reader<E: ByteOrder>(reader: &mut impl ReadBytesExt) -> Result<Self, Error> {
+        let _size = reader.read_u32::<E>()?;
+        let _ = reader.read_u32::<E>()?;
+        let compressed_size = reader.read_u32::<E>()?;
+        let uncompressed_size = reader.read_u32::<E>()?;
+
+        Ok(Self {
+            compressed_size,
+            uncompressed_size,
+        })
+    }
+
+    pub fn is_uncompressed(&self) -> bool {
+        self.compressed_size == UNCOMPRESSED_BLOCK_SIZE
+    }
+
+    /// Number of bytes stored on disk after the block header.
+    pub fn data_size(&self) -> u32 {
+        if self.is_uncompressed() {
+            self.uncompressed_size
+        } else {
+            self.compressed_size
+        }
+    }
+}
+
+//////////////////////////////////////////
+
+struct BlockData(Box<[u8]>);
+
+impl BlockData {
+    pub fn from_reader(
+        reader: &mut impl ReadBytesExt,
+        block_header: &BlockHeader,
+    ) -> Result<Self, Error> {
+        // The size comes from the .dat file, so the buffer only grows as data is read
+        let data_size = block_header.data_size() as u64;
+        let mut data = Vec::new();
+        if reader.take(data_size).read_to_end(&mut data)? as u64 != data_size {
+            return Err(Error::Truncated { path: None });
+        }
+        Ok(Self(data.into_boxed_slice()))
+    }
+}
+
+impl Deref for BlockData {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+//////////////////////////////////////////
+
+#[allow(dead_code)]
+#[derive(Debug)]
+struct CommonHeader {
+    pub size: u32,
+    pub file_type: FileType,
+    pub file_size: u32,
+    pub block_count: u32,
+}
+
+impl CommonHeader {
+    pub fn from_reader<E: ByteOrder>(reader: &mut impl ReadBytesExt) -> Result<Self, Error> {
+        let size = reader.read_u32::<E>()?;
+        let file_type = reader.read_u32::<E>()?;
+        let file_size = reader.read_u32::<E>()?;
+        let _num_blocks = reader.read_u32::<E>()?;
+        let _block_buffer_size = reader.read_u32::<E>()?;
+        let block_count = reader.read_u32::<E>()?;
+
+        let file_type = FileType::try_from(file_type)?;
+
+        Ok(Self {
+            size,
+            file_type,
+            file_size,
+            block_count,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum FileType {
+    Empty = 1,
+    Standard,
+    Model,
+    Texture,
+}
+
+impl TryFrom<u32> for FileType {
+    type Error = Error;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        Ok(match value {
+            1 => FileType::Empty,
+            2 => FileType::Standard,
+            3 => FileType::Model,
+            4 => FileType::Texture,
+            _ => return Err(Error::unknown_value("FileType", value)),
+        })
+    }
+}
+
+//////////////////////////////////////////
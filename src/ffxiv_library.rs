@@ -1,132 +1,953 @@
 use std::{
-    collections::HashMap,
-    error::Error,
-    io::{BufReader, BufWriter, Write},
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
     path::{Path, PathBuf},
+    sync::{Arc, Mutex, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard},
 };
 
+use byteorder::{BigEndian, LittleEndian};
+use memmap2::Mmap;
+use rayon::prelude::*;
+
 use crate::{
-    excel::{ExcelDataFile, ExcelDataRow, ExcelDataType, ExcelHeaderFile, ExcelLanguage},
+    cache::{Cache, CacheKey, CacheStats, CacheValue},
+    error::Error,
+    excel::{ExcelDataFile, ExcelDataRow, ExcelHeaderFile, ExcelLanguage},
+    extract::{sort_jobs, ExtractJob, ExtractReport},
     ffxiv_file::FfxivFile,
-    file_key::FileKey,
-    sqpack::SqPackIndexFile,
+    ffxiv_file_reader::{read_mapped_file, FfxivFileReader},
+    file_key::{FileKey, Repository, REPOSITORIES},
+    journal::Journal,
+    mapped_file::map_open_file,
+    overlay::{FileOrigin, OverlayLayer},
+    path_dictionary::{IndexCoverage, PathDictionary},
+    positional_reader::PositionalReader,
+    source::{DirectorySource, SourceFile, SqPackSource},
+    sqpack::{PlatformId, SqPackIndexFile, SqPackIndexHash, SqPackIndexTableEntry},
+    sqpack_patch::replace_entry,
+    verify::{verify_sqpack_reader, VerifyProblem, VerifyReport},
+    version::GameVersion,
 };
 
-type Reader = BufReader<std::fs::File>;
+type Reader = BufReader<PositionalReader>;
 
+/// An open `.index` or `.datN` file, shared between readers.
+#[derive(Clone)]
+enum DatFile {
+    File(Arc<File>),
+    Mapped(Arc<Mmap>),
+    Bytes(Arc<[u8]>),
+}
+
+/// Reads files from a `sqpack` directory, or any other `SqPackSource`. Indexes are loaded & dat
+/// files opened the first time they're needed, then shared: reads only take `&self`, so a
+/// library can be used from several threads at once, e.g. behind an `Arc`.
 pub struct FfxivLibrary {
-    game_path: PathBuf,
-    index_files: HashMap<FileKey, SqPackIndexFile>,
-    dat_files: HashMap<(FileKey, u32), Reader>,
+    source: Box<dyn SqPackSource>,
+    platform: PlatformId,
+    repository_file_keys: RwLock<HashMap<Repository, Arc<[FileKey]>>>,
+    index_files: RwLock<HashMap<FileKey, Arc<SqPackIndexFile>>>,
+    dat_files: RwLock<HashMap<(FileKey, u32), DatFile>>,
+    memory_mapped: bool,
+    cache: Option<Cache>,
+    path_dictionary: Option<PathDictionary>,
+    overlays: Vec<OverlayLayer>,
+    journal_path: Option<PathBuf>,
 }
 
+// Sharing between threads is part of the API, so losing it should fail the build
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<FfxivLibrary>();
+};
+
 impl FfxivLibrary {
+    /// Opens the `sqpack` directory at `game_path`, detecting which platform its files are for.
+    /// Falls back to `PlatformId::Win32` when no index files are found.
     pub fn new(game_path: impl AsRef<Path>) -> Self {
+        Self::from_source(DirectorySource::new(game_path))
+    }
+
+    pub fn with_platform(game_path: impl AsRef<Path>, platform: PlatformId) -> Self {
+        Self::from_source_with_platform(DirectorySource::new(game_path), platform)
+    }
+
+    /// Reads from any source of SqPack files, e.g. a `MemorySource` or a `ZipSource`, detecting
+    /// which platform its files are for like `new`.
+    pub fn from_source(source: impl SqPackSource + 'static) -> Self {
+        let platform = detect_source_platform(&source).unwrap_or(PlatformId::Win32);
+        Self::from_source_with_platform(source, platform)
+    }
+
+    pub fn from_source_with_platform(
+        source: impl SqPackSource + 'static,
+        platform: PlatformId,
+    ) -> Self {
         Self {
-            game_path: game_path.as_ref().to_path_buf(),
-            index_files: HashMap::new(),
-            dat_files: HashMap::new(),
+            source: Box::new(source),
+            platform,
+            repository_file_keys: RwLock::default(),
+            index_files: RwLock::default(),
+            dat_files: RwLock::default(),
+            memory_mapped: false,
+            cache: None,
+            path_dictionary: None,
+            overlays: Vec::new(),
+            journal_path: None,
         }
     }
 
-    pub fn get_file(&mut self, path: impl AsRef<str>) -> Result<FfxivFile, Box<dyn Error>> {
+    /// Returns the platform of the first `ffxiv` repository index found under `game_path`.
+    pub fn detect_platform(game_path: impl AsRef<Path>) -> Option<PlatformId> {
+        detect_source_platform(&DirectorySource::new(game_path))
+    }
+
+    pub fn platform(&self) -> PlatformId {
+        self.platform
+    }
+
+    pub fn source(&self) -> &dyn SqPackSource {
+        self.source.as_ref()
+    }
+
+    /// Version of the base game, read from `ffxivgame.ver` next to the `sqpack` directory.
+    pub fn version(&self) -> Result<GameVersion, Error> {
+        read_version(self.source.open_game_file("ffxivgame.ver")?)
+    }
+
+    /// Version of the base game & of every installed expansion.
+    pub fn versions(&self) -> Result<BTreeMap<Repository, GameVersion>, Error> {
+        let mut versions = BTreeMap::new();
+        versions.insert(Repository::from(0), self.version()?);
+
+        for repository in self.installed_expansions() {
+            let file_path = format!("{}/{}.ver", repository, repository);
+            versions.insert(repository, read_version(self.source.open(&file_path)?)?);
+        }
+        Ok(versions)
+    }
+
+    /// The expansion repositories which are installed, i.e. have an `exN/exN.ver` file.
+    pub fn installed_expansions(&self) -> Vec<Repository> {
+        REPOSITORIES
+            .iter()
+            .skip(1)
+            .filter(|repository| {
+                self.source
+                    .contains(&format!("{}/{}.ver", repository, repository))
+            })
+            .flat_map(|repository| Repository::try_from(*repository))
+            .collect()
+    }
+
+    pub fn get_file(&self, path: impl AsRef<str>) -> Result<FfxivFile, Error> {
+        Ok(self.get_file_with_origin(path)?.0)
+    }
+
+    /// Like `get_file`, along with the overlay layer or index the file was read from.
+    pub fn get_file_with_origin(
+        &self,
+        path: impl AsRef<str>,
+    ) -> Result<(FfxivFile, FileOrigin), Error> {
+        let path = path.as_ref();
+        let key = CacheKey::File(path.to_string());
+        if let Some(CacheValue::File(file, origin)) = self.cache_get(&key) {
+            return Ok((file, origin));
+        }
+
+        let (file, origin) = self.read_file(path)?;
+        self.cache_insert(key, CacheValue::File(file.clone(), origin.clone()));
+        Ok((file, origin))
+    }
+
+    /// Reads & decompresses a file, bypassing the cache.
+    fn read_file(&self, path: &str) -> Result<(FfxivFile, FileOrigin), Error> {
+        if let Some(origin) = self.overlay_origin(path) {
+            let file = read_overlay_file(path, &origin)?;
+            return Ok((file, origin));
+        }
+
+        let (file_key, entry) = self.index_entry(path)?;
+        let dat_file = self.dat_file(file_key, entry.data_file_id)?;
+        let file = match dat_file.bytes() {
+            Some(data) => read_mapped_file(data, path, &entry, false)?,
+            None => {
+                let mut reader = BufReader::new(dat_file.reader());
+                FfxivFile::from_reader(&mut reader, path, &entry)?
+            }
+        };
+        Ok((file, FileOrigin::SqPack(file_key)))
+    }
+
+    /// Whether a file exists in an overlay layer or in the library's indexes.
+    pub fn contains(&self, path: impl AsRef<str>) -> Result<bool, Error> {
+        match self.file_origin(path) {
+            Ok(_) => Ok(true),
+            Err(Error::PathNotFound(_)) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Whether a file is in the indexes, ignoring any overlay.
+    pub(crate) fn index_contains(&self, path: &str) -> Result<bool, Error> {
+        match self.index_entry(path) {
+            Ok(_) => Ok(true),
+            Err(Error::PathNotFound(_)) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Which overlay layer or index a file would be read from, without reading it.
+    pub fn file_origin(&self, path: impl AsRef<str>) -> Result<FileOrigin, Error> {
         let path = path.as_ref();
-        let file_key = FileKey::new(path);
-        let file_path = format!("{}00.win32", file_key);
-
-        if let std::collections::hash_map::Entry::Vacant(e) = self.index_files.entry(file_key) {
-            let file_path = format!(
-                "{}/{}/{}.index",
-                self.game_path.to_str().unwrap(),
-                file_key.repository,
-                file_path
+        match self.overlay_origin(path) {
+            Some(origin) => Ok(origin),
+            None => Ok(FileOrigin::SqPack(self.index_entry(path)?.0)),
+        }
+    }
+
+    /// Adds a layer of loose files, which is consulted before the indexes, but after any layer
+    /// added before it. The overlay applies to `get_file`, `contains`, the Excel sheets &
+    /// `extract_files`; streaming with `open`, `extract_index` & verification only look at the
+    /// SqPack files.
+    ///
+    /// Cached files aren't read again when a loose file changes, see `set_cache_budget`.
+    pub fn add_overlay(&mut self, layer: OverlayLayer) {
+        self.overlays.push(layer);
+        if let Some(cache) = &self.cache {
+            cache.clear();
+        }
+    }
+
+    pub fn overlays(&self) -> &[OverlayLayer] {
+        &self.overlays
+    }
+
+    pub fn clear_overlays(&mut self) {
+        self.overlays.clear();
+        if let Some(cache) = &self.cache {
+            cache.clear();
+        }
+    }
+
+    /// Opens a file for streaming, decompressing its blocks only as they're read. Like
+    /// `get_file`, a file in an overlay is read from there instead.
+    pub fn open(&self, path: impl AsRef<str>) -> Result<FfxivFileReader<Reader>, Error> {
+        let path = path.as_ref();
+        if let Some(FileOrigin::Overlay { file_path, .. }) = self.overlay_origin(path) {
+            let file = File::open(file_path)?;
+            let size = file.metadata()?.len();
+            let reader = BufReader::new(PositionalReader::new(Arc::new(file)));
+            return Ok(FfxivFileReader::from_loose(reader, path, size));
+        }
+
+        let (file_key, entry) = self.index_entry(path)?;
+
+        let reader = self.dat_reader(file_key, entry.data_file_id)?;
+        FfxivFileReader::new(reader, path, &entry)
+    }
+
+    /// Reads many files at once. Reads are sorted by dat file & offset, so each of rayon's
+    /// threads walks through its share of a dat file in order, and the blocks of every file are
+    /// decompressed in parallel as well.
+    ///
+    /// `on_file` is called from the worker threads, in no particular order, with each path &
+    /// its file, or the reason it couldn't be read.
+    pub fn extract_files<F>(&self, paths: impl IntoIterator<Item = impl AsRef<str>>, on_file: F)
+    where
+        F: Fn(&str, Result<FfxivFile, Error>) + Sync,
+    {
+        let mut jobs = Vec::new();
+        let mut overlay_files = Vec::new();
+        for path in paths {
+            let path = path.as_ref();
+            if let Some(origin) = self.overlay_origin(path) {
+                overlay_files.push((path.to_string(), origin));
+                continue;
+            }
+            match self.index_entry(path) {
+                Ok((file_key, entry)) => jobs.push(ExtractJob {
+                    path: path.to_string(),
+                    file_key,
+                    entry,
+                }),
+                Err(e) => on_file(path, Err(e)),
+            }
+        }
+
+        overlay_files.par_iter().for_each(|(path, origin)| {
+            on_file(path, read_overlay_file(path, origin));
+        });
+        self.extract_jobs(jobs, &on_file);
+    }
+
+    /// Reads every entry of an index, like `extract_files`, named as in `list_index`.
+    pub fn extract_index<F>(&self, file_key: FileKey, on_file: F) -> Result<(), Error>
+    where
+        F: Fn(&str, Result<FfxivFile, Error>) + Sync,
+    {
+        let jobs = self
+            .list_index(file_key)?
+            .into_iter()
+            .map(|(path, entry)| ExtractJob {
+                path,
+                file_key,
+                entry,
+            })
+            .collect();
+        self.extract_jobs(jobs, &on_file);
+        Ok(())
+    }
+
+    /// Every file of an index, once each, sorted by name. Entries which the path dictionary
+    /// can't name are listed as `{file_key}/{hash}`.
+    pub fn list_index(
+        &self,
+        file_key: FileKey,
+    ) -> Result<Vec<(String, SqPackIndexTableEntry)>, Error> {
+        let index_file = self.index_file(file_key)?;
+        let path_dictionary = self.path_dictionary.as_ref();
+
+        // Both the `.index` & `.index2` entries of a file point at the same data, so each
+        // location is listed once, under a name if either entry has one
+        let mut files = HashMap::new();
+        let named = index_file
+            .collisions()
+            .map(|(path, entry)| (Some(path.to_string()), entry));
+        // `.index` entries come first, so unnamed files are named after their `.index` hash
+        let (index1_entries, index2_entries): (Vec<_>, Vec<_>) = index_file
+            .hashed_entries()
+            .partition(|entry| matches!(entry.hash, SqPackIndexHash::FolderFile { .. }));
+        let hashed = index1_entries
+            .into_iter()
+            .chain(index2_entries)
+            .map(|entry| {
+                let path =
+                    path_dictionary.and_then(|path_dictionary| path_dictionary.path_of(entry));
+                (path.map(str::to_string), entry)
+            });
+        for (path, entry) in named.chain(hashed) {
+            let location = (entry.data_file_id, entry.offset);
+            match files.get(&location) {
+                Some((true, _)) => continue,
+                Some(_) if path.is_none() => continue,
+                _ => {}
+            }
+
+            let is_named = path.is_some();
+            let path = path.unwrap_or_else(|| format!("{}/{:?}", file_key, entry.hash));
+            files.insert(location, (is_named, (path, *entry)));
+        }
+
+        let mut files = files
+            .into_values()
+            .map(|(_, file)| file)
+            .collect::<Vec<_>>();
+        files.sort_by(|(a, _), (b, _)| a.cmp(b));
+        Ok(files)
+    }
+
+    /// Writes many files below `output_path`, keeping their SqPack paths, see `extract_files`.
+    pub fn extract_to_directory(
+        &self,
+        paths: impl IntoIterator<Item = impl AsRef<str>>,
+        output_path: impl AsRef<Path>,
+    ) -> ExtractReport {
+        let output_path = output_path.as_ref();
+        let report = Mutex::new(ExtractReport::default());
+        self.extract_files(paths, |path, file| {
+            let mut report = report.lock().unwrap_or_else(PoisonError::into_inner);
+            report.write(output_path, path, file);
+        });
+        report.into_inner().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Loads & returns every index file found in the source.
+    pub fn indexes(&self) -> Result<Vec<(FileKey, Arc<SqPackIndexFile>)>, Error> {
+        let mut indexes = Vec::new();
+        for repository in REPOSITORIES {
+            let repository = Repository::try_from(*repository)?;
+            for file_key in self.repository_file_keys(repository)?.iter() {
+                indexes.push((*file_key, self.index_file(*file_key)?));
+            }
+        }
+        Ok(indexes)
+    }
+
+    /// Sets the list of known paths, used to name the entries of the indexes.
+    pub fn set_path_dictionary(&mut self, path_dictionary: PathDictionary) {
+        self.path_dictionary = Some(path_dictionary);
+    }
+
+    pub fn path_dictionary(&self) -> Option<&PathDictionary> {
+        self.path_dictionary.as_ref()
+    }
+
+    /// Paths of every file in an index which are known to the path dictionary, sorted.
+    pub fn file_paths(&self, file_key: FileKey) -> Result<Vec<String>, Error> {
+        let index_file = self.index_file(file_key)?;
+
+        let mut paths = index_file
+            .collisions()
+            .map(|(path, _)| path.to_string())
+            .collect::<BTreeSet<_>>();
+        if let Some(path_dictionary) = &self.path_dictionary {
+            paths.extend(
+                index_file
+                    .hashed_entries()
+                    .filter_map(|entry| path_dictionary.path_of(entry))
+                    .map(|path| path.to_string()),
             );
-            e.insert(SqPackIndexFile::from_file(file_path)?);
         }
-        let index_file = self.index_files.get(&file_key).unwrap();
+        Ok(paths.into_iter().collect())
+    }
+
+    /// How many entries of each index the path dictionary can name.
+    pub fn coverage(&self) -> Result<Vec<IndexCoverage>, Error> {
+        let empty = PathDictionary::new();
+        let path_dictionary = self.path_dictionary.as_ref().unwrap_or(&empty);
+        Ok(self
+            .indexes()?
+            .into_iter()
+            .map(|(file_key, index_file)| {
+                IndexCoverage::new(file_key, &index_file, path_dictionary)
+            })
+            .collect())
+    }
+
+    /// Switches between reading `.index` & `.datN` files through memory maps, where blocks are
+    /// inflated straight from the mapped bytes, and through positional reads (the default).
+    /// Files which are already open are closed. Only files on disk are mapped, as sources like
+    /// `MemorySource` already hand out their bytes.
+    ///
+    /// Mapped files mustn't be truncated by anything else while the library (or a reader from
+    /// `open`) is using them; `replace_file` & `restore_backup` close them first.
+    pub fn set_memory_mapped(&mut self, memory_mapped: bool) {
+        self.memory_mapped = memory_mapped;
+        self.clear_caches();
+    }
+
+    pub fn is_memory_mapped(&self) -> bool {
+        self.memory_mapped
+    }
+
+    /// Keeps up to `budget` bytes of decompressed files & parsed Excel sheets in memory, so
+    /// repeated reads of the same path are served from there, dropping the least recently used
+    /// values first. A budget of 0 turns the cache off, which is the default.
+    pub fn set_cache_budget(&mut self, budget: usize) {
+        self.cache = (budget > 0).then(|| Cache::new(budget));
+    }
+
+    /// Hit & miss counts of the cache, if it's turned on.
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.cache.as_ref().map(Cache::stats)
+    }
+
+    /// Sets where `replace_file` keeps its backups. Defaults to `.backup` under the game path.
+    pub fn set_journal_path(&mut self, journal_path: impl AsRef<Path>) {
+        self.journal_path = Some(journal_path.as_ref().to_path_buf());
+    }
+
+    /// Replaces the contents of an existing file. The new data is appended to the end of the
+    /// category's last `.datN` file, or to a new one when that's full, and both indexes are
+    /// updated to point at it. The original state is kept in a journal, see `restore_backup`.
+    ///
+    /// Only a library reading from a directory can be changed.
+    pub fn replace_file(&mut self, path: impl AsRef<str>, contents: &[u8]) -> Result<(), Error> {
+        let path = path.as_ref();
+        let (file_key, _) = self.index_entry(path)?;
+        let base_path = self.game_path()?.join(self.sqpack_file_path(file_key));
+        let mut journal = self.journal()?;
+
+        // Cached readers, entries & files would be out of date
+        if let Some(cache) = &self.cache {
+            cache.clear();
+        }
+        self.index_files
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&file_key);
+        self.dat_files
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain(|(key, _), _| *key != file_key);
 
-        let entry = index_file.entry_from_path(path).ok_or(std::io::Error::new(
-            std::io::ErrorKind::NotFound,
-            format!("Path not found: {}", path),
-        ))?;
+        if self.platform.is_big_endian() {
+            replace_entry::<BigEndian>(&mut journal, &base_path, self.platform, path, contents)
+        } else {
+            replace_entry::<LittleEndian>(&mut journal, &base_path, self.platform, path, contents)
+        }
+    }
 
-        if let std::collections::hash_map::Entry::Vacant(e) =
-            self.dat_files.entry((file_key, entry.data_file_id))
+    /// Undoes every change made by `replace_file` since the journal was started. Returns false
+    /// if there was nothing to undo.
+    pub fn restore_backup(&mut self) -> Result<bool, Error> {
+        let journal = self.journal()?;
+        if journal.is_empty() {
+            return Ok(false);
+        }
+
+        self.clear_caches();
+        journal.restore()?;
+        Ok(true)
+    }
+
+    /// Checks the digests of every index & dat file in the source, and that every index entry
+    /// decompresses to its declared size.
+    pub fn verify(&self) -> Result<VerifyReport, Error> {
+        let file_keys = self
+            .indexes()?
+            .into_iter()
+            .map(|(file_key, _)| file_key)
+            .collect::<Vec<_>>();
+
+        let mut report = VerifyReport::default();
+        for file_key in file_keys {
+            report.append(self.verify_index(file_key)?);
+        }
+        Ok(report)
+    }
+
+    /// Checks a single index, the dat files it refers to & every one of its entries.
+    pub fn verify_index(&self, file_key: FileKey) -> Result<VerifyReport, Error> {
+        let index_path = self.index_file_path(file_key);
+        let mut report = self.verify_sqpack_file(&index_path)?;
+        report.append(self.verify_sqpack_file(&format!("{}2", index_path))?);
+
+        let entries = self
+            .index_file(file_key)?
+            .entries()
+            .copied()
+            .collect::<Vec<_>>();
+
+        let data_file_ids = entries
+            .iter()
+            .map(|entry| entry.data_file_id)
+            .collect::<BTreeSet<_>>();
+        for data_file_id in data_file_ids {
+            let dat_path = self.dat_file_path(file_key, data_file_id);
+            if self.source.contains(&dat_path) {
+                report.append(self.verify_sqpack_file(&dat_path)?);
+            }
+        }
+
+        for entry in entries {
+            report.entries_checked += 1;
+            if let Some(problem) = self.verify_entry(file_key, entry)? {
+                report.problems.push(problem);
+            }
+        }
+        Ok(report)
+    }
+
+    /// Checks that a single file decompresses to its declared size.
+    pub fn verify_file(&self, path: impl AsRef<str>) -> Result<VerifyReport, Error> {
+        let (file_key, entry) = self.index_entry(path.as_ref())?;
+
+        let mut report = VerifyReport {
+            entries_checked: 1,
+            ..Default::default()
+        };
+        if let Some(problem) = self.verify_entry(file_key, entry)? {
+            report.problems.push(problem);
+        }
+        Ok(report)
+    }
+
+    fn verify_entry(
+        &self,
+        file_key: FileKey,
+        entry: SqPackIndexTableEntry,
+    ) -> Result<Option<VerifyProblem>, Error> {
+        let data_file_id = entry.data_file_id;
+        if !self
+            .source
+            .contains(&self.dat_file_path(file_key, data_file_id))
         {
-            let file_path = format!(
-                "{}/{}/{}.dat{}",
-                self.game_path.to_str().unwrap(),
-                file_key.repository,
-                file_path,
-                entry.data_file_id,
-            );
-            e.insert(BufReader::new(std::fs::File::open(file_path)?));
+            return Ok(Some(VerifyProblem::MissingDataFile {
+                file_key,
+                data_file_id,
+            }));
+        }
+
+        let reader = self.dat_reader(file_key, data_file_id)?;
+        let dat_size = reader.get_ref().file_len()?;
+        if entry.offset as u64 >= dat_size {
+            return Ok(Some(VerifyProblem::EntryOutOfBounds { file_key, entry }));
+        }
+
+        let name = format!("{}/{:?}", file_key, entry.hash);
+        let file = match FfxivFileReader::new(reader, name, &entry) {
+            Ok(file_reader) => file_reader,
+            Err(error) => {
+                return Ok(Some(VerifyProblem::InvalidEntry {
+                    file_key,
+                    entry,
+                    error,
+                }))
+            }
+        };
+
+        let expected = file.declared_len();
+        let placeholder = file.is_placeholder();
+        let file = match file.into_file() {
+            Ok(file) => file,
+            Err(error) => {
+                return Ok(Some(VerifyProblem::InvalidEntry {
+                    file_key,
+                    entry,
+                    error,
+                }))
+            }
+        };
+
+        let actual = file.len() as u64;
+        if !placeholder && actual != expected {
+            return Ok(Some(VerifyProblem::SizeMismatch {
+                file_key,
+                entry,
+                expected,
+                actual,
+            }));
+        }
+        Ok(None)
+    }
+
+    /// The first overlay layer which has a file.
+    fn overlay_origin(&self, path: &str) -> Option<FileOrigin> {
+        self.overlays
+            .iter()
+            .enumerate()
+            .find_map(|(layer, overlay)| {
+                let file_path = overlay.find(path)?;
+                Some(FileOrigin::Overlay { layer, file_path })
+            })
+    }
+
+    fn verify_sqpack_file(&self, path: &str) -> Result<VerifyReport, Error> {
+        let mut reader = BufReader::new(self.open_file(path)?.reader());
+        let file_path = match self.source.directory() {
+            Some(directory) => directory.join(path),
+            None => PathBuf::from(path),
+        };
+        verify_sqpack_reader(&mut reader, &file_path)
+    }
+
+    /// The directory being read, for the operations which change files.
+    fn game_path(&self) -> Result<&Path, Error> {
+        self.source
+            .directory()
+            .ok_or_else(|| Error::Unsupported("changing files outside of a directory".to_string()))
+    }
+
+    fn journal(&self) -> Result<Journal, Error> {
+        let game_path = self.game_path()?;
+        let journal_path = match &self.journal_path {
+            Some(journal_path) => journal_path.clone(),
+            None => game_path.join(".backup"),
+        };
+        Journal::open(game_path, journal_path)
+    }
+
+    fn cache_get(&self, key: &CacheKey) -> Option<CacheValue> {
+        self.cache.as_ref().and_then(|cache| cache.get(key))
+    }
+
+    fn cache_insert(&self, key: CacheKey, value: CacheValue) {
+        if let Some(cache) = &self.cache {
+            cache.insert(key, value);
+        }
+    }
+
+    /// Drops every loaded index & open dat file, so they're read again from the source.
+    fn clear_caches(&mut self) {
+        if let Some(cache) = &self.cache {
+            cache.clear();
+        }
+        self.index_files
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+        self.dat_files
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+        self.repository_file_keys
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+
+    /// Loads an index the first time it's needed. Indexes are parsed without holding the lock,
+    /// so two threads may both parse the same one, in which case the first is kept.
+    fn index_file(&self, file_key: FileKey) -> Result<Arc<SqPackIndexFile>, Error> {
+        if let Some(index_file) = read_lock(&self.index_files).get(&file_key) {
+            return Ok(index_file.clone());
+        }
+
+        let index_path = self.index_file_path(file_key);
+        let index2_path = format!("{}2", index_path);
+        let index1 = self.read_index_part(&index_path, SqPackIndexFile::from_reader1)?;
+        let index2 = self.read_index_part(&index2_path, SqPackIndexFile::from_reader2)?;
+        let index_file = Arc::new(SqPackIndexFile::merge(index1, index2));
+        Ok(write_lock(&self.index_files)
+            .entry(file_key)
+            .or_insert(index_file)
+            .clone())
+    }
+
+    /// Every chunk's key for every category present in a repository, in order.
+    fn repository_file_keys(&self, repository: Repository) -> Result<Arc<[FileKey]>, Error> {
+        if let Some(file_keys) = read_lock(&self.repository_file_keys).get(&repository) {
+            return Ok(file_keys.clone());
+        }
+
+        let suffix = format!(".{}.index", self.platform.file_extension());
+        let mut file_keys = self
+            .source
+            .file_names(&repository.to_string())?
+            .into_iter()
+            .filter(|file_name| file_name.ends_with(&suffix))
+            .filter_map(|file_name| FileKey::from_file_name(&file_name))
+            .collect::<Vec<_>>();
+        file_keys.sort();
+        Ok(write_lock(&self.repository_file_keys)
+            .entry(repository)
+            .or_insert_with(|| file_keys.into())
+            .clone())
+    }
+
+    fn index_entry(&self, path: &str) -> Result<(FileKey, SqPackIndexTableEntry), Error> {
+        let path_key = FileKey::new(path)?;
+        let file_keys = self.repository_file_keys(path_key.repository)?;
+        let file_keys = file_keys
+            .iter()
+            .filter(|file_key| file_key.category == path_key.category)
+            .copied();
+
+        for file_key in file_keys {
+            if let Some(entry) = self.index_file(file_key)?.entry_from_path(path) {
+                return Ok((file_key, *entry));
+            }
         }
-        let reader = self
-            .dat_files
-            .get_mut(&(file_key, entry.data_file_id))
-            .unwrap();
+        Err(Error::PathNotFound(path.to_string()))
+    }
+
+    fn extract_jobs<F>(&self, mut jobs: Vec<ExtractJob>, on_file: &F)
+    where
+        F: Fn(&str, Result<FfxivFile, Error>) + Sync,
+    {
+        // Rayon splits the jobs into contiguous runs, so each thread's reads stay sequential
+        sort_jobs(&mut jobs);
+        jobs.par_iter().for_each(|job| {
+            let file = self
+                .dat_file(job.file_key, job.entry.data_file_id)
+                .and_then(|dat_file| match dat_file.bytes() {
+                    Some(data) => read_mapped_file(data, &job.path, &job.entry, true),
+                    None => FfxivFileReader::new(dat_file.reader(), &job.path, &job.entry)?
+                        .into_file_parallel(),
+                });
+            on_file(&job.path, file);
+        });
+    }
+
+    /// A new reader over a dat file, see `dat_file`.
+    fn dat_reader(&self, file_key: FileKey, data_file_id: u32) -> Result<Reader, Error> {
+        Ok(BufReader::new(
+            self.dat_file(file_key, data_file_id)?.reader(),
+        ))
+    }
+
+    /// Opens (or maps) a dat file the first time it's needed, then shares it between readers.
+    fn dat_file(&self, file_key: FileKey, data_file_id: u32) -> Result<DatFile, Error> {
+        let key = (file_key, data_file_id);
+        if let Some(file) = read_lock(&self.dat_files).get(&key) {
+            return Ok(file.clone());
+        }
+
+        let file = self.open_file(&self.dat_file_path(file_key, data_file_id))?;
+        Ok(write_lock(&self.dat_files)
+            .entry(key)
+            .or_insert(file)
+            .clone())
+    }
+
+    /// Opens a file of the source, mapping it into memory if asked to & it's on disk.
+    fn open_file(&self, path: &str) -> Result<DatFile, Error> {
+        Ok(match self.source.open(path)? {
+            SourceFile::File(file) if self.memory_mapped => {
+                DatFile::Mapped(Arc::new(map_open_file(&file)?))
+            }
+            SourceFile::File(file) => DatFile::File(Arc::new(file)),
+            SourceFile::Bytes(bytes) => DatFile::Bytes(bytes),
+        })
+    }
+
+    /// Parses one of an index's two files.
+    fn read_index_part<T>(
+        &self,
+        path: &str,
+        parse: fn(&mut Reader) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let mut reader = BufReader::new(self.open_file(path)?.reader());
+        parse(&mut reader).map_err(|e| e.with_path(path))
+    }
+
+    fn index_file_path(&self, file_key: FileKey) -> String {
+        format!("{}.index", self.sqpack_file_path(file_key))
+    }
+
+    /// Path of a category's SqPack files, without the `.index` or `.datN` extension.
+    fn sqpack_file_path(&self, file_key: FileKey) -> String {
+        format!(
+            "{}/{}.{}",
+            file_key.repository,
+            file_key,
+            self.platform.file_extension()
+        )
+    }
 
-        let file = FfxivFile::from_reader(reader, path, entry)?;
-        Ok(file)
+    fn dat_file_path(&self, file_key: FileKey, data_file_id: u32) -> String {
+        format!("{}.dat{}", self.sqpack_file_path(file_key), data_file_id)
     }
 
-    pub fn get_table_data(
-        &mut self,
+    /// Rows of a sheet, e.g. `exd/Item`, in English where it's translated.
+    pub fn get_table_data(&self, path: impl AsRef<str>) -> Result<Vec<ExcelDataRow>, Error> {
+        self.get_table_data_in(path, ExcelLanguage::English)
+    }
+
+    /// Rows of a sheet in a language. Sheets which aren't translated are read whatever the
+    /// language; asking for one which a translated sheet doesn't have is an error.
+    pub fn get_table_data_in(
+        &self,
         path: impl AsRef<str>,
-    ) -> Result<Vec<ExcelDataRow>, Box<dyn Error>> {
+        language: ExcelLanguage,
+    ) -> Result<Vec<ExcelDataRow>, Error> {
         let path = path.as_ref();
-        let header_file_path = format!("{}.exh", path);
-        let excel_file = ExcelHeaderFile::from_file(self.get_file(&header_file_path)?)?;
+        let excel_file = self.get_excel_header(path)?;
+
+        let languages = &excel_file.languages;
+        let language_ending = if languages.contains(&language) {
+            language.as_country_code()
+        } else if languages.is_empty() || languages.contains(&ExcelLanguage::None) {
+            ExcelLanguage::None.as_country_code()
+        } else {
+            return Err(
+                Error::unknown_value("ExcelLanguage", format!("{:?}", language))
+                    .with_path(format!("{}.exh", path)),
+            );
+        };
 
         let mut vec = Vec::new();
         for excel_page in &excel_file.pages {
-            let language_ending = excel_file
-                .languages
-                .contains(&ExcelLanguage::English)
-                .then_some(ExcelLanguage::English)
-                .map(|lang| lang.as_country_code())
-                .unwrap_or_else(|| ExcelLanguage::None.as_country_code());
-
             let path = format!(
                 "{}_{}{}.exd",
                 path, excel_page.start_row_id, language_ending
             );
-            let file = match self.get_file(&path) {
-                Ok(v) => v,
-                Err(e) => {
-                    println!("Error for {}: {:?}", path, e);
-                    continue;
+            let key = CacheKey::ExcelData(path.clone());
+            let excel_data_file = match self.cache_get(&key) {
+                Some(CacheValue::ExcelData(excel_data_file)) => excel_data_file,
+                _ => {
+                    // Pages may be left out of the SqPack, but any other failure is an error
+                    let file = match self.read_file(&path) {
+                        Ok((file, _)) => file,
+                        Err(Error::PathNotFound(_)) => continue,
+                        Err(e) => return Err(e),
+                    };
+
+                    let excel_data_file = Arc::new(ExcelDataFile::from_file(file, &excel_file)?);
+                    self.cache_insert(key, CacheValue::ExcelData(excel_data_file.clone()));
+                    excel_data_file
                 }
             };
-
-            let excel_data_file = ExcelDataFile::from_file(file, &excel_file)?;
-            vec.append(&mut excel_data_file.into_inner());
+            vec.extend_from_slice(&excel_data_file);
         }
 
         Ok(vec)
     }
 
-    pub fn write_to_csv(&mut self, path: impl AsRef<str>) -> Result<(), Box<dyn Error>> {
-        let path = path.as_ref();
-        let data: Vec<ExcelDataRow> = self.get_table_data(path)?;
-
-        let mut writer = BufWriter::new(std::fs::File::create(format!("out/{}.csv", path))?);
-        for row in data {
-            let entries = [format!("{}", row.row_info().row_id)]
-                .into_iter()
-                .chain(row.iter().map(|entry| match entry {
-                    ExcelDataType::String(v) => format!("\"{}\"", v),
-                    ExcelDataType::I64(v) => format!("{}", v),
-                    ExcelDataType::U64(v) => format!("{}", v),
-                    ExcelDataType::F32(v) => format!("{}", v),
-                }))
-                .collect::<Vec<_>>()
-                .join(",");
-
-            writeln!(writer, "{}", entries)?;
+    /// Parses the `.exh` header of a sheet, e.g. `exd/Item`.
+    pub fn get_excel_header(&self, path: impl AsRef<str>) -> Result<Arc<ExcelHeaderFile>, Error> {
+        let header_file_path = format!("{}.exh", path.as_ref());
+        let key = CacheKey::ExcelHeader(header_file_path.clone());
+        if let Some(CacheValue::ExcelHeader(header)) = self.cache_get(&key) {
+            return Ok(header);
         }
 
+        let header = Arc::new(ExcelHeaderFile::from_file(
+            self.read_file(&header_file_path)?.0,
+        )?);
+        self.cache_insert(key, CacheValue::ExcelHeader(header.clone()));
+        Ok(header)
+    }
+
+    /// Writes a sheet as CSV, one row per line, see `ExcelDataRow::to_csv`.
+    pub fn write_to_csv(&self, path: impl AsRef<str>, writer: impl Write) -> Result<(), Error> {
+        let mut writer = BufWriter::new(writer);
+        for row in self.get_table_data(path)? {
+            writeln!(writer, "{}", row.to_csv())?;
+        }
+        writer.flush()?;
         Ok(())
     }
 }
+
+// The caches only ever hold fully-loaded values, so they're still usable after a panic
+fn read_lock<T>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    lock.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn write_lock<T>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    lock.write().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Reads a loose file found by `FfxivLibrary::overlay_origin`.
+fn read_overlay_file(path: &str, origin: &FileOrigin) -> Result<FfxivFile, Error> {
+    let FileOrigin::Overlay { file_path, .. } = origin else {
+        return Err(Error::PathNotFound(path.to_string()));
+    };
+    let contents = std::fs::read(file_path)?;
+    Ok(FfxivFile::new(path.to_string(), contents.into(), false))
+}
+
+/// Returns the platform of the first `ffxiv` repository index found in a source.
+fn read_version(file: SourceFile) -> Result<GameVersion, Error> {
+    let mut contents = Vec::new();
+    match file {
+        SourceFile::File(mut file) => {
+            file.read_to_end(&mut contents)?;
+        }
+        SourceFile::Bytes(bytes) => contents.extend_from_slice(&bytes),
+    }
+    String::from_utf8(contents)?.parse()
+}
+
+fn detect_source_platform(source: &dyn SqPackSource) -> Option<PlatformId> {
+    let file_names = source.file_names("ffxiv").ok()?;
+    PlatformId::ALL.into_iter().find(|platform| {
+        let suffix = format!(".{}.index", platform.file_extension());
+        file_names
+            .iter()
+            .any(|file_name| file_name.ends_with(&suffix))
+    })
+}
+
+impl DatFile {
+    fn reader(&self) -> PositionalReader {
+        match self {
+            DatFile::File(file) => PositionalReader::new(file.clone()),
+            DatFile::Mapped(mapped) => PositionalReader::from_mapped(mapped.clone()),
+            DatFile::Bytes(bytes) => PositionalReader::from_bytes(bytes.clone()),
+        }
+    }
+
+    /// The whole file, when it's in memory.
+    fn bytes(&self) -> Option<&[u8]> {
+        match self {
+            DatFile::File(_) => None,
+            DatFile::Mapped(mapped) => Some(mapped),
+            DatFile::Bytes(bytes) => Some(bytes),
+        }
+    }
+}
@@ -1,20 +1,28 @@
 use std::fmt::Display;
 
+use crate::error::Error;
+
 pub static REPOSITORIES: &[&str] = &[
     "ffxiv", "ex1", "ex2", "ex3", "ex4", "ex5", "ex6", "ex7", "ex8", "ex9",
 ];
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct FileKey {
     pub category: Category,
     pub repository: Repository,
+    /// Large categories are split across several numbered files, e.g. `020401`, `020402`.
+    pub chunk: u8,
 }
 
 impl FileKey {
-    pub fn new(path: impl AsRef<str>) -> Self {
+    /// Returns the key of the first chunk for a path. The path may be in any of the
+    /// category's chunks.
+    pub fn new(path: impl AsRef<str>) -> Result<Self, Error> {
         let path = path.as_ref();
-        let (category, rest) = path.split_once("/").unwrap();
-        let category = Category::from(category);
+        let (category, rest) = path
+            .split_once("/")
+            .ok_or_else(|| Error::PathNotFound(path.to_string()))?;
+        let category = Category::try_from(category).map_err(|e| e.with_path(path))?;
 
         let repository = match rest.split_once("/") {
             None => "ffxiv", // only a file_name e.g. exd/item.exh
@@ -25,25 +33,41 @@ impl FileKey {
             .find(|repo| **repo == repository)
             .cloned()
             .unwrap_or("ffxiv");
-        let repository = Repository::from(repository);
+        let repository = Repository::try_from(repository)?;
 
-        Self {
+        Ok(Self {
             category,
             repository,
-        }
+            chunk: 0,
+        })
+    }
+
+    /// Parses the key from a SqPack file name, e.g. `0a0000.win32.index`.
+    pub fn from_file_name(file_name: impl AsRef<str>) -> Option<Self> {
+        let file_name = file_name.as_ref();
+        let category = u32::from_str_radix(file_name.get(0..2)?, 16).ok()?;
+        let repository = usize::from_str_radix(file_name.get(2..4)?, 16).ok()?;
+        let chunk = u8::from_str_radix(file_name.get(4..6)?, 16).ok()?;
+
+        Some(Self {
+            category: Category::try_from(category).ok()?,
+            repository: Repository::from(repository),
+            chunk,
+        })
     }
 }
 
 impl Display for FileKey {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{:02x}", Into::<usize>::into(self.category))?;
-        write!(f, "{:02x}", self.repository.0)
+        write!(f, "{:02x}", self.repository.0)?;
+        write!(f, "{:02x}", self.chunk)
     }
 }
 
 ///////////////////////////////////////////////
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Category {
     Common,
     BgCommon,
@@ -88,9 +112,11 @@ impl Display for Category {
     }
 }
 
-impl From<&str> for Category {
-    fn from(value: &str) -> Self {
-        match value {
+impl TryFrom<&str> for Category {
+    type Error = Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Ok(match value {
             "common" => Category::Common,
             "bgcommon" => Category::BgCommon,
             "bg" => Category::Bg,
@@ -106,14 +132,16 @@ impl From<&str> for Category {
             "music" => Category::Music,
             "sqpack_test" => Category::SqPackTest,
             "debug" => Category::Debug,
-            _ => panic!("Unexpected value for Category: {}", value),
-        }
+            _ => return Err(Error::unknown_value("Category", value)),
+        })
     }
 }
 
-impl From<usize> for Category {
-    fn from(value: usize) -> Self {
-        match value {
+impl TryFrom<usize> for Category {
+    type Error = Error;
+
+    fn try_from(value: usize) -> Result<Self, Self::Error> {
+        Ok(match value {
             0 => Category::Common,
             1 => Category::BgCommon,
             2 => Category::Bg,
@@ -129,14 +157,16 @@ impl From<usize> for Category {
             12 => Category::Music,
             18 => Category::SqPackTest,
             19 => Category::Debug,
-            _ => panic!("Unknown Category: {}", value),
-        }
+            _ => return Err(Error::unknown_value("Category", value)),
+        })
     }
 }
 
-impl From<u32> for Category {
-    fn from(value: u32) -> Self {
-        Self::from(value as usize)
+impl TryFrom<u32> for Category {
+    type Error = Error;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        Self::try_from(value as usize)
     }
 }
 
@@ -164,7 +194,7 @@ impl From<Category> for usize {
 
 ///////////////////////////////////////////////
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Repository(usize);
 
 impl From<usize> for Repository {
@@ -173,9 +203,11 @@ impl From<usize> for Repository {
     }
 }
 
-impl From<&str> for Repository {
-    fn from(value: &str) -> Self {
-        Repository(match value {
+impl TryFrom<&str> for Repository {
+    type Error = Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Ok(Repository(match value {
             "ffxiv" => 0,
             "ex1" => 1,
             "ex2" => 2,
@@ -186,14 +218,16 @@ impl From<&str> for Repository {
             "ex7" => 7,
             "ex8" => 8,
             "ex9" => 9,
-            _ => panic!("Unexpected Repository value: {}", value),
-        })
+            _ => return Err(Error::unknown_value("Repository", value)),
+        }))
     }
 }
 
-impl From<String> for Repository {
-    fn from(value: String) -> Self {
-        Repository::from(value.as_ref())
+impl TryFrom<String> for Repository {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Repository::try_from(value.as_ref())
     }
 }
 
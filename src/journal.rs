@@ -0,0 +1,217 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::error::Error;
+
+//////////////////////////////////////////
+
+const JOURNAL_FILE_NAME: &str = "journal.txt";
+
+/// Records how to undo changes made to SqPack files, so the original state can be restored.
+///
+/// Only the first change to each file is recorded: restoring always goes back to the state
+/// from before the journal was started. Each record is written before the change it covers.
+pub(crate) struct Journal {
+    /// The directory which changed files are relative to.
+    game_path: PathBuf,
+    /// The directory holding the journal & backups.
+    path: PathBuf,
+    records: Vec<JournalRecord>,
+}
+
+enum JournalRecord {
+    /// The file was copied to the journal directory before being changed.
+    Backup(PathBuf),
+    /// Data was appended to the file, whose original length & headers are kept.
+    Append(PathBuf, u64),
+    /// The file didn't exist.
+    Created(PathBuf),
+}
+
+impl JournalRecord {
+    fn file_path(&self) -> &Path {
+        match self {
+            JournalRecord::Backup(file_path)
+            | JournalRecord::Append(file_path, _)
+            | JournalRecord::Created(file_path) => file_path,
+        }
+    }
+}
+
+impl Journal {
+    pub fn open(game_path: impl AsRef<Path>, path: impl AsRef<Path>) -> Result<Self, Error> {
+        let mut journal = Self {
+            game_path: game_path.as_ref().to_path_buf(),
+            path: path.as_ref().to_path_buf(),
+            records: Vec::new(),
+        };
+
+        let journal_file = journal.path.join(JOURNAL_FILE_NAME);
+        if journal_file.is_file() {
+            for line in BufReader::new(File::open(journal_file)?).lines() {
+                let line = line?;
+                let invalid = || Error::unknown_value("journal record", &line);
+                let mut fields = line.splitn(3, '\t');
+                let (Some(kind), Some(length), Some(file_path)) =
+                    (fields.next(), fields.next(), fields.next())
+                else {
+                    return Err(invalid());
+                };
+
+                let file_path = PathBuf::from(file_path);
+                journal.records.push(match kind {
+                    "backup" => JournalRecord::Backup(file_path),
+                    "append" => {
+                        JournalRecord::Append(file_path, length.parse().map_err(|_| invalid())?)
+                    }
+                    "created" => JournalRecord::Created(file_path),
+                    _ => return Err(invalid()),
+                });
+            }
+        }
+
+        Ok(journal)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Copies a file before it's rewritten.
+    pub fn back_up(&mut self, file_path: &Path) -> Result<(), Error> {
+        let relative_path = self.relative_path(file_path)?;
+        if self.is_recorded(&relative_path) {
+            return Ok(());
+        }
+
+        let backup_path = self.path.join(&relative_path);
+        std::fs::create_dir_all(backup_path.parent().unwrap_or(&self.path))?;
+        std::fs::copy(file_path, backup_path)?;
+        self.push(JournalRecord::Backup(relative_path))
+    }
+
+    /// Keeps the length & the first `header_size` bytes of a file before data is appended.
+    pub fn back_up_append(&mut self, file_path: &Path, header_size: usize) -> Result<(), Error> {
+        let relative_path = self.relative_path(file_path)?;
+        if self.is_recorded(&relative_path) {
+            return Ok(());
+        }
+
+        let mut file = File::open(file_path)?;
+        let length = file.metadata()?.len();
+        let mut header = vec![0; header_size];
+        file.read_exact(&mut header)?;
+
+        let backup_path = self.path.join(&relative_path);
+        std::fs::create_dir_all(backup_path.parent().unwrap_or(&self.path))?;
+        std::fs::write(backup_path, header)?;
+        self.push(JournalRecord::Append(relative_path, length))
+    }
+
+    /// Notes that a file is about to be created.
+    pub fn record_created(&mut self, file_path: &Path) -> Result<(), Error> {
+        let relative_path = self.relative_path(file_path)?;
+        if self.is_recorded(&relative_path) {
+            return Ok(());
+        }
+        self.push(JournalRecord::Created(relative_path))
+    }
+
+    /// Undoes every recorded change, newest first, then removes the journal.
+    pub fn restore(self) -> Result<(), Error> {
+        for record in self.records.iter().rev() {
+            let file_path = self.game_path.join(record.file_path());
+            let backup_path = self.path.join(record.file_path());
+            match record {
+                JournalRecord::Backup(_) => {
+                    std::fs::copy(backup_path, file_path)?;
+                }
+                JournalRecord::Append(_, length) => {
+                    let header = std::fs::read(backup_path)?;
+                    let mut file = OpenOptions::new().write(true).open(file_path)?;
+                    file.set_len(*length)?;
+                    file.seek(SeekFrom::Start(0))?;
+                    file.write_all(&header)?;
+                }
+                JournalRecord::Created(_) => {
+                    if file_path.is_file() {
+                        std::fs::remove_file(file_path)?;
+                    }
+                }
+            }
+        }
+
+        // Only the journal's own files are removed, the directory may hold others
+        for record in &self.records {
+            if let JournalRecord::Backup(_) | JournalRecord::Append(..) = record {
+                let backup_path = self.path.join(record.file_path());
+                if backup_path.is_file() {
+                    std::fs::remove_file(&backup_path)?;
+                }
+                self.remove_empty_dirs(&backup_path);
+            }
+        }
+        let journal_file = self.path.join(JOURNAL_FILE_NAME);
+        if journal_file.is_file() {
+            std::fs::remove_file(journal_file)?;
+        }
+        if self.path.is_dir() && std::fs::read_dir(&self.path)?.next().is_none() {
+            std::fs::remove_dir(&self.path)?;
+        }
+        Ok(())
+    }
+
+    /// Removes the folders between a backup & the journal directory which are left empty.
+    fn remove_empty_dirs(&self, backup_path: &Path) {
+        let mut dir = backup_path.parent();
+        while let Some(path) = dir.filter(|path| *path != self.path && path.starts_with(&self.path))
+        {
+            // Fails for folders which aren't empty, which are kept
+            if std::fs::remove_dir(path).is_err() {
+                break;
+            }
+            dir = path.parent();
+        }
+    }
+
+    fn relative_path(&self, file_path: &Path) -> Result<PathBuf, Error> {
+        file_path
+            .strip_prefix(&self.game_path)
+            .map(Path::to_path_buf)
+            .map_err(|_| Error::PathNotFound(file_path.to_string_lossy().to_string()))
+    }
+
+    fn is_recorded(&self, relative_path: &Path) -> bool {
+        self.records
+            .iter()
+            .any(|record| record.file_path() == relative_path)
+    }
+
+    fn push(&mut self, record: JournalRecord) -> Result<(), Error> {
+        let (kind, length) = match &record {
+            JournalRecord::Backup(_) => ("backup", 0),
+            JournalRecord::Append(_, length) => ("append", *length),
+            JournalRecord::Created(_) => ("created", 0),
+        };
+
+        std::fs::create_dir_all(&self.path)?;
+        let mut journal_file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path.join(JOURNAL_FILE_NAME))?;
+        writeln!(
+            journal_file,
+            "{}\t{}\t{}",
+            kind,
+            length,
+            record.file_path().display()
+        )?;
+        journal_file.sync_all()?;
+
+        self.records.push(record);
+        Ok(())
+    }
+}
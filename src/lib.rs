@@ -1,10 +1,50 @@
+mod cache;
+mod discovery;
+mod error;
 mod excel;
+mod extract;
 mod ffxiv_file;
+mod ffxiv_file_reader;
 mod ffxiv_library;
 mod file_key;
+mod journal;
+mod mapped_file;
+mod overlay;
+mod path_dictionary;
+mod path_discovery;
+mod positional_reader;
+mod source;
 mod sqpack;
+mod sqpack_patch;
+mod sqpack_writer;
+mod verify;
+mod version;
+mod zipatch;
 
+pub use cache::CacheStats;
+pub use discovery::GameInstallation;
+pub use error::Error;
+pub use excel::{ExcelDataFile, ExcelDataRow, ExcelDataType, ExcelHeaderFile, ExcelLanguage};
+pub use extract::ExtractReport;
 pub use ffxiv_file::FfxivFile;
+pub use ffxiv_file_reader::FfxivFileReader;
 pub use ffxiv_library::FfxivLibrary;
-pub use file_key::FileKey;
-pub use sqpack::{SqPackIndexFile, SqPackIndexTableEntry};
+pub use file_key::{Category, FileKey, Repository};
+pub use overlay::{FileOrigin, OverlayLayer};
+pub use path_dictionary::{IndexCoverage, PathDictionary};
+pub use path_discovery::{IconPaths, LevelPaths, ModelPaths, PathDiscovery, PathGenerator};
+pub use positional_reader::PositionalReader;
+#[cfg(feature = "zip")]
+pub use source::ZipSource;
+pub use source::{DirectorySource, MemorySource, SourceFile, SqPackSource};
+pub use sqpack::{
+    PlatformId, SqPackDataHeader, SqPackFileType, SqPackHeader, SqPackIndexFile, SqPackIndexHash,
+    SqPackIndexHeader, SqPackIndexHeaders, SqPackIndexSegment, SqPackIndexTableEntry,
+};
+pub use sqpack_writer::{SqPackWriter, DEFAULT_MAX_DAT_SIZE};
+pub use verify::{verify_sqpack_file, VerifyProblem, VerifyReport};
+pub use version::GameVersion;
+pub use zipatch::{
+    ApplyOption, FileHeaderChunk, SqpkCommand, SqpkFileCommand, SqpkFileKind, SqpkFileOperation,
+    SqpkHeaderKind, SqpkTargetFile, ZiPatchApplier, ZiPatchChunk, ZiPatchReader,
+};
@@ -0,0 +1,22 @@
+use std::{fs::File, path::Path};
+
+use memmap2::Mmap;
+
+use crate::error::Error;
+
+//////////////////////////////////////////
+
+/// Maps a whole file into memory, read-only.
+///
+/// The mapping stays valid for as long as the `Mmap` lives, but its contents follow the file:
+/// SqPack files must not be truncated while mapped. `FfxivLibrary` drops its mappings before
+/// changing any file itself.
+pub(crate) fn map_file(file_path: impl AsRef<Path>) -> Result<Mmap, Error> {
+    map_open_file(&File::open(file_path)?)
+}
+
+/// Like `map_file`, for a file which is already open.
+pub(crate) fn map_open_file(file: &File) -> Result<Mmap, Error> {
+    // SAFETY: the file is only read through the mapping, see above
+    Ok(unsafe { Mmap::map(file)? })
+}
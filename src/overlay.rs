@@ -0,0 +1,270 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::file_key::FileKey;
+
+//////////////////////////////////////////
+
+/// Loose files on disk which are read instead of the SqPack files, like a mod loader does,
+/// see `FfxivLibrary::add_overlay`. Game paths are matched without regard to case, as in the
+/// indexes.
+#[derive(Debug, Clone)]
+pub struct OverlayLayer {
+    kind: OverlayKind,
+}
+
+#[derive(Debug, Clone)]
+enum OverlayKind {
+    /// Files laid out by their game path, e.g. `{directory}/exd/root.exl`.
+    Directory(PathBuf),
+    /// Disk paths by lowercase game path.
+    Redirects(HashMap<String, PathBuf>),
+}
+
+impl OverlayLayer {
+    /// Serves any game path which has a file at the same relative path below `directory`.
+    /// Paths are looked up as given first, then in lowercase.
+    pub fn from_directory(directory: impl AsRef<Path>) -> Self {
+        Self {
+            kind: OverlayKind::Directory(directory.as_ref().to_path_buf()),
+        }
+    }
+
+    /// Serves each game path from the file it's redirected to.
+    pub fn from_redirects<P, Q>(redirects: impl IntoIterator<Item = (P, Q)>) -> Self
+    where
+        P: AsRef<str>,
+        Q: AsRef<Path>,
+    {
+        let redirects = redirects
+            .into_iter()
+            .map(|(game_path, disk_path)| {
+                (
+                    normalize_game_path(game_path.as_ref()),
+                    disk_path.as_ref().to_path_buf(),
+                )
+            })
+            .collect();
+        Self {
+            kind: OverlayKind::Redirects(redirects),
+        }
+    }
+
+    /// The file on disk which serves `path`, if this layer has one.
+    pub fn find(&self, path: &str) -> Option<PathBuf> {
+        match &self.kind {
+            OverlayKind::Directory(directory) => {
+                // Game paths are relative, so none of them may point outside of the directory
+                let path = path.trim_start_matches(['/', '\\']);
+                if !is_contained_path(path) {
+                    return None;
+                }
+                let file_path = directory.join(path);
+                if file_path.is_file() {
+                    return Some(file_path);
+                }
+                let file_path = directory.join(normalize_game_path(path));
+                file_path.is_file().then_some(file_path)
+            }
+            OverlayKind::Redirects(redirects) => redirects.get(&normalize_game_path(path)).cloned(),
+        }
+    }
+}
+
+/// Whether a path stays below the directory it's joined to, i.e. it's relative & has no `..`
+/// components. Both `/` & `\` count as separators.
+pub(crate) fn is_contained_path(path: &str) -> bool {
+    !path.starts_with(['/', '\\'])
+        && !path.split(['/', '\\']).any(|component| component == "..")
+        && Path::new(path)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+}
+
+fn normalize_game_path(path: &str) -> String {
+    path.replace('\\', "/")
+        .trim_start_matches('/')
+        .to_lowercase()
+}
+
+//////////////////////////////////////////
+
+/// Where `FfxivLibrary` read a file from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileOrigin {
+    /// A loose file, from the overlay layer at `layer` in the order they were added.
+    Overlay { layer: usize, file_path: PathBuf },
+    /// The SqPack files of an index.
+    SqPack(FileKey),
+}
+
+impl Display for FileOrigin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FileOrigin::Overlay { layer, file_path } => {
+                write!(f, "overlay {} ({})", layer, file_path.display())
+            }
+            FileOrigin::SqPack(file_key) => write!(f, "sqpack {}", file_key),
+        }
+    }
+}
+
+//////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ffxiv_library::FfxivLibrary, sqpack::PlatformId, sqpack_writer::SqPackWriter};
+
+    fn write_file(file_path: &Path, contents: &[u8]) {
+        std::fs::create_dir_all(file_path.parent().unwrap()).unwrap();
+        std::fs::write(file_path, contents).unwrap();
+    }
+
+    #[test]
+    fn directory_layer() {
+        let dir = tempfile::tempdir().unwrap();
+        write_file(&dir.path().join("exd/root.exl"), b"root");
+        write_file(&dir.path().join("exd/Item.exh"), b"item");
+        let layer = OverlayLayer::from_directory(dir.path());
+
+        // As given, then in lowercase
+        let root = Some(dir.path().join("exd/root.exl"));
+        assert_eq!(layer.find("exd/root.exl"), root);
+        assert_eq!(layer.find("EXD/ROOT.exl"), root);
+        assert_eq!(layer.find("/exd/root.exl"), root);
+        assert_eq!(
+            layer.find("exd/Item.exh"),
+            Some(dir.path().join("exd/Item.exh"))
+        );
+        assert_eq!(layer.find("exd/missing.exh"), None);
+
+        // Nothing outside of the directory
+        let inner = OverlayLayer::from_directory(dir.path().join("exd"));
+        assert_eq!(inner.find("../exd/root.exl"), None);
+        assert_eq!(
+            inner.find("root.exl"),
+            Some(dir.path().join("exd/root.exl"))
+        );
+    }
+
+    #[test]
+    fn redirects_layer() {
+        let layer = OverlayLayer::from_redirects([
+            ("EXD\\Root.exl", "/mods/root.exl"),
+            ("/exd/item.exh", "/mods/item.exh"),
+        ]);
+        assert_eq!(
+            layer.find("exd/root.exl"),
+            Some(PathBuf::from("/mods/root.exl"))
+        );
+        assert_eq!(
+            layer.find("exd\\ITEM.exh"),
+            Some(PathBuf::from("/mods/item.exh"))
+        );
+        assert_eq!(layer.find("exd/action.exh"), None);
+    }
+
+    #[test]
+    fn contained_paths() {
+        for path in ["exd/root.exl", "./exd/root.exl", "exd/..data"] {
+            assert!(is_contained_path(path), "{}", path);
+        }
+        for path in [
+            "/exd/root.exl",
+            "\\exd",
+            "../root.exl",
+            "exd\\..\\..\\root.exl",
+        ] {
+            assert!(!is_contained_path(path), "{}", path);
+        }
+    }
+
+    #[test]
+    fn precedence_and_origin() {
+        let dir = tempfile::tempdir().unwrap();
+        let sqpack_path = dir.path().join("sqpack");
+        let mut writer = SqPackWriter::new(PlatformId::Win32);
+        writer
+            .add_file("exd/root.exl", b"sqpack root".to_vec())
+            .unwrap();
+        writer
+            .add_file("exd/item.exh", b"sqpack item".to_vec())
+            .unwrap();
+        writer
+            .add_file("exd/action.exh", b"sqpack action".to_vec())
+            .unwrap();
+        writer.write(&sqpack_path).unwrap();
+
+        let first = dir.path().join("first");
+        write_file(&first.join("exd/root.exl"), b"first root");
+        let second_root = dir.path().join("second/root.exl");
+        let second_item = dir.path().join("second/item.exh");
+        let second_status = dir.path().join("second/status.exh");
+        write_file(&second_root, b"second root");
+        write_file(&second_item, b"second item");
+        write_file(&second_status, b"second status");
+
+        let mut library = FfxivLibrary::with_platform(&sqpack_path, PlatformId::Win32);
+        library.add_overlay(OverlayLayer::from_directory(&first));
+        library.add_overlay(OverlayLayer::from_redirects([
+            ("exd/root.exl", &second_root),
+            ("exd/item.exh", &second_item),
+            ("exd/status.exh", &second_status),
+        ]));
+
+        let file_key = FileKey::new("exd/root.exl").unwrap();
+        let expected = [
+            // The first layer added wins
+            (
+                "exd/root.exl",
+                &b"first root"[..],
+                FileOrigin::Overlay {
+                    layer: 0,
+                    file_path: first.join("exd/root.exl"),
+                },
+            ),
+            (
+                "exd/item.exh",
+                b"second item",
+                FileOrigin::Overlay {
+                    layer: 1,
+                    file_path: second_item.clone(),
+                },
+            ),
+            // Overlays may add files the indexes don't have
+            (
+                "exd/status.exh",
+                b"second status",
+                FileOrigin::Overlay {
+                    layer: 1,
+                    file_path: second_status.clone(),
+                },
+            ),
+            (
+                "exd/action.exh",
+                b"sqpack action",
+                FileOrigin::SqPack(file_key),
+            ),
+        ];
+        for (path, contents, origin) in expected {
+            let (file, file_origin) = library.get_file_with_origin(path).unwrap();
+            assert_eq!(&file[..], contents, "{}", path);
+            assert_eq!(file_origin, origin, "{}", path);
+            assert_eq!(library.file_origin(path).unwrap(), origin, "{}", path);
+        }
+        assert_eq!(
+            FileOrigin::SqPack(file_key).to_string(),
+            format!("sqpack {}", file_key)
+        );
+
+        library.clear_overlays();
+        let (file, origin) = library.get_file_with_origin("exd/root.exl").unwrap();
+        assert_eq!(&file[..], b"sqpack root");
+        assert_eq!(origin, FileOrigin::SqPack(file_key));
+        assert!(!library.contains("exd/status.exh").unwrap());
+    }
+}
@@ -0,0 +1,327 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use crate::{
+    error::Error,
+    file_key::FileKey,
+    sqpack::{SqPackIndexFile, SqPackIndexHash, SqPackIndexTableEntry},
+};
+
+//////////////////////////////////////////
+
+/// A list of known game paths, used to turn index hashes back into paths.
+#[derive(Debug, Default)]
+pub struct PathDictionary {
+    paths: Vec<String>,
+    hashes: HashMap<SqPackIndexHash, usize>,
+}
+
+impl PathDictionary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_file(file_path: impl AsRef<Path>) -> Result<Self, Error> {
+        let file_path = file_path.as_ref();
+        let reader = BufReader::new(File::open(file_path)?);
+        Self::from_reader(reader).map_err(|e| e.with_path(file_path.to_string_lossy()))
+    }
+
+    /// Reads one path per line. CSV lines (e.g. ResLogger exports) are also accepted: the
+    /// `path` column is used when there's a header naming it, otherwise the last column.
+    /// Fields may be quoted, with `""` for a quote inside them.
+    pub fn from_reader(reader: impl BufRead) -> Result<Self, Error> {
+        let mut dictionary = Self::new();
+        let mut path_column = None;
+
+        for (line_number, line) in reader.lines().enumerate() {
+            let line = line?;
+            let fields = split_csv_line(&line)?;
+
+            if line_number == 0 {
+                path_column = fields
+                    .iter()
+                    .position(|field| field.eq_ignore_ascii_case("path"));
+                if path_column.is_some() {
+                    continue;
+                }
+            }
+
+            let path = match path_column {
+                Some(column) => fields.get(column),
+                None => fields.last(),
+            };
+            if let Some(path) = path {
+                dictionary.insert(path);
+            }
+        }
+
+        Ok(dictionary)
+    }
+
+    /// Writes the paths one per line, in the format read by `from_reader`.
+    pub fn write(&self, writer: &mut impl Write) -> Result<(), Error> {
+        for path in &self.paths {
+            writeln!(writer, "{}", path)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_to_file(&self, file_path: impl AsRef<Path>) -> Result<(), Error> {
+        let mut writer = BufWriter::new(File::create(file_path)?);
+        self.write(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Adds a path, returning false if it isn't a valid game path or is already known.
+    pub fn insert(&mut self, path: impl AsRef<str>) -> bool {
+        let path = path.as_ref().to_lowercase();
+        let Some((hash1, hash2)) = SqPackIndexHash::from_path(&path) else {
+            return false;
+        };
+        if self
+            .hashes
+            .get(&hash2)
+            .is_some_and(|&i| self.paths[i] == path)
+        {
+            return false;
+        }
+
+        let index = self.paths.len();
+        self.paths.push(path);
+        self.hashes.entry(hash1).or_insert(index);
+        self.hashes.entry(hash2).or_insert(index);
+        true
+    }
+
+    /// The path for either an `.index` or an `.index2` hash.
+    pub fn get(&self, hash: &SqPackIndexHash) -> Option<&str> {
+        self.hashes
+            .get(hash)
+            .map(|&index| self.paths[index].as_str())
+    }
+
+    pub fn path_of(&self, entry: &SqPackIndexTableEntry) -> Option<&str> {
+        self.get(&entry.hash)
+    }
+
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.paths.iter().map(|path| path.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+}
+
+/// Splits a line of CSV into its trimmed fields. Quoted fields may hold commas; a quote which
+/// is never closed is an error, as the line can't be split reliably.
+fn split_csv_line(line: &str) -> Result<Vec<String>, Error> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' if quoted => quoted = false,
+            // Quotes only open a field, once any spaces before them are skipped
+            '"' if field.trim().is_empty() => {
+                field.clear();
+                quoted = true;
+            }
+            ',' if !quoted => fields.push(std::mem::take(&mut field).trim().to_string()),
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(Error::unknown_value("CSV line", line));
+    }
+    fields.push(field.trim().to_string());
+    Ok(fields)
+}
+
+//////////////////////////////////////////
+
+/// How many of an index's entries a `PathDictionary` can name.
+#[derive(Debug, Clone, Copy)]
+pub struct IndexCoverage {
+    pub file_key: FileKey,
+    /// Entries of the `.index` file, hashed by folder & file name.
+    pub index1_entries: usize,
+    pub index1_named: usize,
+    /// Entries of the `.index2` file, hashed by full path.
+    pub index2_entries: usize,
+    pub index2_named: usize,
+}
+
+impl IndexCoverage {
+    pub(crate) fn new(
+        file_key: FileKey,
+        index_file: &SqPackIndexFile,
+        dictionary: &PathDictionary,
+    ) -> Self {
+        let mut coverage = Self {
+            file_key,
+            index1_entries: 0,
+            index1_named: 0,
+            index2_entries: 0,
+            index2_named: 0,
+        };
+
+        let entries = index_file
+            .hashed_entries()
+            .map(|entry| (entry, dictionary.path_of(entry).is_some()))
+            // Colliding entries are stored alongside their full path, so they're always named
+            .chain(index_file.collisions().map(|(_, entry)| (entry, true)));
+
+        for (entry, named) in entries {
+            let (count, named_count) = match entry.hash {
+                SqPackIndexHash::FolderFile { .. } => {
+                    (&mut coverage.index1_entries, &mut coverage.index1_named)
+                }
+                SqPackIndexHash::FullPath(_) => {
+                    (&mut coverage.index2_entries, &mut coverage.index2_named)
+                }
+            };
+            *count += 1;
+            *named_count += named as usize;
+        }
+        coverage
+    }
+
+    pub fn entries(&self) -> usize {
+        self.index1_entries + self.index2_entries
+    }
+
+    pub fn named(&self) -> usize {
+        self.index1_named + self.index2_named
+    }
+
+    /// Fraction of entries which have a known path, from 0 to 1.
+    pub fn ratio(&self) -> f64 {
+        match self.entries() {
+            0 => 1.0,
+            entries => self.named() as f64 / entries as f64,
+        }
+    }
+}
+
+impl Display for IndexCoverage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {}/{} index, {}/{} index2 ({:.1}%)",
+            self.file_key,
+            self.index1_named,
+            self.index1_entries,
+            self.index2_named,
+            self.index2_entries,
+            self.ratio() * 100.0
+        )
+    }
+}
+
+//////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ffxiv_library::FfxivLibrary, sqpack::PlatformId, sqpack_writer::SqPackWriter};
+
+    fn paths(dictionary: &PathDictionary) -> Vec<&str> {
+        dictionary.paths().collect()
+    }
+
+    #[test]
+    fn one_path_per_line() {
+        let input = "exd/root.exl\n  ui/icon/000000/000001.tex \n\nnot a path\nexd/ROOT.exl\n";
+        let dictionary = PathDictionary::from_reader(input.as_bytes()).unwrap();
+        assert_eq!(
+            paths(&dictionary),
+            ["exd/root.exl", "ui/icon/000000/000001.tex"]
+        );
+    }
+
+    #[test]
+    fn csv_path_column() {
+        let input = "\
+            IndexId,Path,FolderHash\n\
+            1,exd/root.exl,5\n\
+            \"2\", \"exd/item.exh\" ,\"6,7\"\n\
+            \"3,4\",\"exd/action.exh\",8\n";
+        let dictionary = PathDictionary::from_reader(input.as_bytes()).unwrap();
+        assert_eq!(
+            paths(&dictionary),
+            ["exd/root.exl", "exd/item.exh", "exd/action.exh"]
+        );
+    }
+
+    #[test]
+    fn csv_last_column() {
+        let input = "1,2,exd/root.exl\n\"a,b\",\"exd/item.exh\"\n";
+        let dictionary = PathDictionary::from_reader(input.as_bytes()).unwrap();
+        assert_eq!(paths(&dictionary), ["exd/root.exl", "exd/item.exh"]);
+    }
+
+    #[test]
+    fn csv_quoting() {
+        assert_eq!(split_csv_line("a, b ,c").unwrap(), ["a", "b", "c"]);
+        assert_eq!(
+            split_csv_line("\"a,b\",\"say \"\"hi\"\"\",").unwrap(),
+            ["a,b", "say \"hi\"", ""]
+        );
+        assert_eq!(split_csv_line("").unwrap(), [""]);
+        assert!(matches!(
+            split_csv_line("1,\"exd/root.exl"),
+            Err(Error::UnknownValue { .. })
+        ));
+        assert!(PathDictionary::from_reader("1,\"exd/root.exl,2\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn index_coverage() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut writer = SqPackWriter::new(PlatformId::Win32);
+        for path in ["exd/root.exl", "exd/item.exh", "exd/action.exh"] {
+            writer.add_file(path, vec![1; 10]).unwrap();
+        }
+        writer.write(dir.path()).unwrap();
+
+        let mut library = FfxivLibrary::with_platform(dir.path(), PlatformId::Win32);
+        let mut dictionary = PathDictionary::new();
+        dictionary.insert("exd/root.exl");
+        dictionary.insert("exd/missing.exh");
+        library.set_path_dictionary(dictionary);
+
+        let [coverage] = library.coverage().unwrap()[..] else {
+            panic!("expected a single index");
+        };
+        assert_eq!(coverage.file_key, FileKey::new("exd/root.exl").unwrap());
+        assert_eq!((coverage.index1_entries, coverage.index1_named), (3, 1));
+        assert_eq!((coverage.index2_entries, coverage.index2_named), (3, 1));
+        assert_eq!((coverage.entries(), coverage.named()), (6, 2));
+        assert!((coverage.ratio() - 1.0 / 3.0).abs() < 1e-9);
+        assert!(coverage
+            .to_string()
+            .ends_with(": 1/3 index, 1/3 index2 (33.3%)"));
+
+        let empty = PathDictionary::new();
+        let index_file = &library.indexes().unwrap()[0].1;
+        let coverage = IndexCoverage::new(coverage.file_key, index_file, &empty);
+        assert_eq!(coverage.named(), 0);
+    }
+}
@@ -0,0 +1,283 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    error::Error,
+    excel::{ExcelDataRow, ExcelDataType},
+    ffxiv_library::FfxivLibrary,
+    path_dictionary::PathDictionary,
+};
+
+//////////////////////////////////////////
+
+/// Races with their own equipment models, as used in `c{race}e{set}` file names.
+static RACE_CODES: &[&str] = &[
+    "0101", "0104", "0201", "0204", "0301", "0304", "0401", "0404", "0501", "0504", "0601", "0604",
+    "0701", "0704", "0801", "0804", "0901", "0904", "1001", "1004", "1101", "1104", "1201", "1204",
+    "1301", "1304", "1401", "1404", "1501", "1504", "1601", "1604", "1701", "1704", "1801", "1804",
+];
+
+static EQUIPMENT_SLOTS: &[&str] = &["met", "top", "glv", "dwn", "sho"];
+static ACCESSORY_SLOTS: &[&str] = &["ear", "nek", "wrs", "rir", "ril"];
+
+/// Layer groups found next to a zone's `.lvb`.
+static LEVEL_GROUPS: &[&str] = &[
+    "bg",
+    "planevent",
+    "planlive",
+    "planmap",
+    "planner",
+    "sound",
+    "vfx",
+];
+
+//////////////////////////////////////////
+
+/// Generates candidate paths from the rows of an Excel sheet.
+pub trait PathGenerator {
+    /// The sheet to read, e.g. `Item`.
+    fn sheet(&self) -> &str;
+
+    /// Passes every candidate path to `candidate`. Candidates may repeat.
+    fn generate(&self, rows: &[ExcelDataRow], candidate: &mut dyn FnMut(String));
+}
+
+/// Reads sheets, runs path generators over their rows & keeps the candidates which exist in
+/// the library's indexes. Files only found in an overlay aren't kept, as the dictionary names
+/// index entries.
+#[derive(Default)]
+pub struct PathDiscovery {
+    generators: Vec<Box<dyn PathGenerator>>,
+}
+
+impl PathDiscovery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Icons of `Item`, `Action` & `Status`, equipment models of `Item` & levels of `TerritoryType`.
+    pub fn with_default_generators() -> Self {
+        let mut discovery = Self::new();
+        for sheet in ["Item", "Action", "Status"] {
+            discovery.add_generator(IconPaths::new(sheet));
+        }
+        discovery.add_generator(ModelPaths::new("Item"));
+        discovery.add_generator(LevelPaths::new("TerritoryType"));
+        discovery
+    }
+
+    pub fn add_generator(&mut self, generator: impl PathGenerator + 'static) {
+        self.generators.push(Box::new(generator));
+    }
+
+    /// Runs every generator, returning the candidates found in the library's indexes. Sheets
+    /// which don't exist in the library are skipped.
+    pub fn run(&self, library: &FfxivLibrary) -> Result<PathDictionary, Error> {
+        let mut sheets = BTreeMap::<&str, Vec<&dyn PathGenerator>>::new();
+        for generator in &self.generators {
+            sheets
+                .entry(generator.sheet())
+                .or_default()
+                .push(generator.as_ref());
+        }
+
+        let mut dictionary = PathDictionary::new();
+        for (sheet, generators) in sheets {
+            let rows = match library.get_table_data(format!("exd/{}", sheet)) {
+                Ok(rows) => rows,
+                Err(Error::PathNotFound(_)) => continue,
+                Err(e) => return Err(e),
+            };
+
+            // Candidates are checked as they're generated, as there can be millions of them
+            let mut result = Ok(());
+            for generator in generators {
+                generator.generate(&rows, &mut |candidate| {
+                    if result.is_ok() {
+                        result = library
+                            .index_contains(&candidate)
+                            .map(|found| found && dictionary.insert(candidate))
+                            .map(|_| ());
+                    }
+                });
+            }
+            result?;
+        }
+        Ok(dictionary)
+    }
+}
+
+//////////////////////////////////////////
+
+/// `ui/icon/XXXXXX/YYYYYY.tex` paths, including their HQ & high resolution variants.
+///
+/// Sheet layouts change between game versions, so every integer column is treated as a
+/// possible icon id unless specific columns are given.
+pub struct IconPaths {
+    sheet: String,
+    columns: Option<Vec<usize>>,
+}
+
+impl IconPaths {
+    pub fn new(sheet: impl Into<String>) -> Self {
+        Self {
+            sheet: sheet.into(),
+            columns: None,
+        }
+    }
+
+    pub fn with_columns(mut self, columns: impl Into<Vec<usize>>) -> Self {
+        self.columns = Some(columns.into());
+        self
+    }
+}
+
+impl PathGenerator for IconPaths {
+    fn sheet(&self) -> &str {
+        &self.sheet
+    }
+
+    fn generate(&self, rows: &[ExcelDataRow], candidate: &mut dyn FnMut(String)) {
+        let icon_ids = integer_cells(rows, self.columns.as_deref())
+            .filter(|&icon_id| icon_id != 0 && icon_id <= 999_999);
+
+        for icon_id in icon_ids {
+            let folder = icon_id / 1000 * 1000;
+            for variant in ["", "hq/"] {
+                for suffix in ["", "_hr1"] {
+                    candidate(format!(
+                        "ui/icon/{:06}/{}{:06}{}.tex",
+                        folder, variant, icon_id, suffix
+                    ));
+                }
+            }
+        }
+    }
+}
+
+//////////////////////////////////////////
+
+/// Equipment, accessory & weapon models, from the packed model ids of e.g. `Item.ModelMain`.
+///
+/// Sheet layouts change between game versions, so every integer column is treated as a
+/// possible model id unless specific columns are given.
+pub struct ModelPaths {
+    sheet: String,
+    columns: Option<Vec<usize>>,
+}
+
+impl ModelPaths {
+    pub fn new(sheet: impl Into<String>) -> Self {
+        Self {
+            sheet: sheet.into(),
+            columns: None,
+        }
+    }
+
+    pub fn with_columns(mut self, columns: impl Into<Vec<usize>>) -> Self {
+        self.columns = Some(columns.into());
+        self
+    }
+}
+
+impl PathGenerator for ModelPaths {
+    fn sheet(&self) -> &str {
+        &self.sheet
+    }
+
+    fn generate(&self, rows: &[ExcelDataRow], candidate: &mut dyn FnMut(String)) {
+        // Gear is packed as set | variant << 16, weapons as set | body << 16 | variant << 32
+        let mut sets = BTreeSet::new();
+        let mut weapons = BTreeSet::new();
+        for model_id in integer_cells(rows, self.columns.as_deref()) {
+            let set = model_id & 0xFFFF;
+            let body = (model_id >> 16) & 0xFFFF;
+            if set == 0 || set > 9999 {
+                continue;
+            }
+            sets.insert(set);
+            if body != 0 && body <= 9999 {
+                weapons.insert((set, body));
+            }
+        }
+
+        for set in sets {
+            for race in RACE_CODES {
+                for slot in EQUIPMENT_SLOTS {
+                    candidate(format!(
+                        "chara/equipment/e{:04}/model/c{}e{:04}_{}.mdl",
+                        set, race, set, slot
+                    ));
+                }
+                for slot in ACCESSORY_SLOTS {
+                    candidate(format!(
+                        "chara/accessory/a{:04}/model/c{}a{:04}_{}.mdl",
+                        set, race, set, slot
+                    ));
+                }
+            }
+        }
+
+        for (set, body) in weapons {
+            candidate(format!(
+                "chara/weapon/w{:04}/obj/body/b{:04}/model/w{:04}b{:04}.mdl",
+                set, body, set, body
+            ));
+        }
+    }
+}
+
+//////////////////////////////////////////
+
+/// `bg/{Bg}.lvb` & the layer groups next to it, from e.g. `TerritoryType.Bg`.
+///
+/// Any string column shaped like a level path, e.g. `ffxiv/fst_f1/fld/f1f1/level/f1f1`, is used.
+pub struct LevelPaths {
+    sheet: String,
+}
+
+impl LevelPaths {
+    pub fn new(sheet: impl Into<String>) -> Self {
+        Self {
+            sheet: sheet.into(),
+        }
+    }
+}
+
+impl PathGenerator for LevelPaths {
+    fn sheet(&self) -> &str {
+        &self.sheet
+    }
+
+    fn generate(&self, rows: &[ExcelDataRow], candidate: &mut dyn FnMut(String)) {
+        for cell in rows.iter().flat_map(|row| row.iter()) {
+            let ExcelDataType::String(bg) = cell else {
+                continue;
+            };
+            let Some((level_directory, _)) = bg.rsplit_once("/level/") else {
+                continue;
+            };
+
+            candidate(format!("bg/{}.lvb", bg));
+            for group in LEVEL_GROUPS {
+                candidate(format!("bg/{}/level/{}.lgb", level_directory, group));
+            }
+        }
+    }
+}
+
+//////////////////////////////////////////
+
+/// Distinct non-negative integer cells of a sheet, limited to the given columns if any.
+fn integer_cells(rows: &[ExcelDataRow], columns: Option<&[usize]>) -> impl Iterator<Item = u64> {
+    let cells = rows.iter().flat_map(|row| {
+        row.iter()
+            .enumerate()
+            .filter(|(column, _)| columns.is_none_or(|columns| columns.contains(column)))
+            .filter_map(|(_, cell)| match cell {
+                ExcelDataType::U64(value) => Some(*value),
+                ExcelDataType::I64(value) => u64::try_from(*value).ok(),
+                _ => None,
+            })
+    });
+    cells.collect::<BTreeSet<_>>().into_iter()
+}
@@ -0,0 +1,110 @@
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    sync::Arc,
+};
+
+use memmap2::Mmap;
+
+//////////////////////////////////////////
+
+/// A `Read + Seek` cursor over a shared file. Reads go through `pread` (or `seek_read` on
+/// Windows), which leave the file's own position alone, or are copied from a memory-mapped
+/// file or buffer, so any number of readers can use the same handle from different threads.
+#[derive(Debug, Clone)]
+pub struct PositionalReader {
+    source: Source,
+    position: u64,
+}
+
+#[derive(Debug, Clone)]
+enum Source {
+    File(Arc<File>),
+    Mapped(Arc<Mmap>),
+    Bytes(Arc<[u8]>),
+}
+
+impl PositionalReader {
+    pub fn new(file: Arc<File>) -> Self {
+        Self {
+            source: Source::File(file),
+            position: 0,
+        }
+    }
+
+    pub fn from_mapped(mapped: Arc<Mmap>) -> Self {
+        Self {
+            source: Source::Mapped(mapped),
+            position: 0,
+        }
+    }
+
+    pub fn from_bytes(bytes: Arc<[u8]>) -> Self {
+        Self {
+            source: Source::Bytes(bytes),
+            position: 0,
+        }
+    }
+
+    /// Size of the underlying file.
+    pub fn file_len(&self) -> std::io::Result<u64> {
+        match &self.source {
+            Source::File(file) => Ok(file.metadata()?.len()),
+            Source::Mapped(mapped) => Ok(mapped.len() as u64),
+            Source::Bytes(bytes) => Ok(bytes.len() as u64),
+        }
+    }
+
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> std::io::Result<usize> {
+        match &self.source {
+            Source::File(file) => read_file_at(file, buf, offset),
+            Source::Mapped(mapped) => Ok(read_slice_at(mapped, buf, offset)),
+            Source::Bytes(bytes) => Ok(read_slice_at(bytes, buf, offset)),
+        }
+    }
+}
+
+fn read_slice_at(data: &[u8], buf: &mut [u8], offset: u64) -> usize {
+    let start = usize::try_from(offset)
+        .unwrap_or(usize::MAX)
+        .min(data.len());
+    let count = buf.len().min(data.len() - start);
+    buf[..count].copy_from_slice(&data[start..start + count]);
+    count
+}
+
+#[cfg(unix)]
+fn read_file_at(file: &File, buf: &mut [u8], offset: u64) -> std::io::Result<usize> {
+    std::os::unix::fs::FileExt::read_at(file, buf, offset)
+}
+
+#[cfg(windows)]
+fn read_file_at(file: &File, buf: &mut [u8], offset: u64) -> std::io::Result<usize> {
+    std::os::windows::fs::FileExt::seek_read(file, buf, offset)
+}
+
+impl Read for PositionalReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.read_at(buf, self.position)?;
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl Seek for PositionalReader {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.file_len()?.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+
+        self.position = position.ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(self.position)
+    }
+}
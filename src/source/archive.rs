@@ -0,0 +1,103 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufReader, Read, Seek},
+    path::Path,
+    sync::{Mutex, PoisonError},
+};
+
+use zip::{result::ZipError, ZipArchive};
+
+use crate::error::Error;
+
+use super::{
+    files_in_folder, normalize_path, not_found, relative_path, sqpack_root, ArchivePath,
+    SourceFile, SqPackSource,
+};
+
+//////////////////////////////////////////
+
+/// A zip archive of a `sqpack` directory, or of any folder above it. Files are decompressed
+/// into memory as they're opened, which `FfxivLibrary` does once for each index & dat file.
+pub struct ZipSource<R> {
+    archive: Mutex<ZipArchive<R>>,
+    /// Index of each file in the archive, by its path relative to the `sqpack` directory.
+    files: BTreeMap<String, usize>,
+    /// Index of each file of the game directory above the `sqpack` directory, by name.
+    game_files: BTreeMap<String, usize>,
+}
+
+impl ZipSource<BufReader<File>> {
+    pub fn from_file(file_path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::new(BufReader::new(File::open(file_path)?))
+    }
+}
+
+impl<R: Read + Seek> ZipSource<R> {
+    pub fn new(reader: R) -> Result<Self, Error> {
+        let archive = ZipArchive::new(reader).map_err(zip_error)?;
+        let names = (0..archive.len())
+            .filter_map(|index| Some((normalize_path(archive.name_for_index(index)?), index)))
+            .collect::<Vec<_>>();
+
+        let root = sqpack_root(names.iter().map(|(name, _)| name.as_str()))
+            .ok_or_else(|| Error::PathNotFound("ffxiv".to_string()))?;
+        let mut files = BTreeMap::new();
+        let mut game_files = BTreeMap::new();
+        for (name, index) in names {
+            match relative_path(&root, &name) {
+                Some(ArchivePath::SqPack(path)) => files.insert(path, index),
+                Some(ArchivePath::Game(file_name)) => game_files.insert(file_name, index),
+                None => None,
+            };
+        }
+
+        Ok(Self {
+            archive: Mutex::new(archive),
+            files,
+            game_files,
+        })
+    }
+}
+
+impl<R: Read + Seek + Send> SqPackSource for ZipSource<R> {
+    fn file_names(&self, folder: &str) -> Result<Vec<String>, Error> {
+        Ok(files_in_folder(&self.files, folder))
+    }
+
+    fn open(&self, path: &str) -> Result<SourceFile, Error> {
+        self.read(*self.files.get(path).ok_or_else(|| not_found(path))?)
+    }
+
+    fn contains(&self, path: &str) -> bool {
+        self.files.contains_key(path)
+    }
+
+    fn open_game_file(&self, file_name: &str) -> Result<SourceFile, Error> {
+        let index = *self
+            .game_files
+            .get(file_name)
+            .ok_or_else(|| not_found(file_name))?;
+        self.read(index)
+    }
+}
+
+impl<R: Read + Seek> ZipSource<R> {
+    /// Decompresses a file of the archive, given its index.
+    fn read(&self, index: usize) -> Result<SourceFile, Error> {
+        // A failed read leaves nothing half-done in the archive, so a poisoned lock is fine
+        let mut archive = self.archive.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut file = archive.by_index(index).map_err(zip_error)?;
+        // The size in the archive isn't trusted, so the buffer grows as it's read
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)?;
+        Ok(SourceFile::Bytes(contents.into()))
+    }
+}
+
+fn zip_error(error: ZipError) -> Error {
+    match error {
+        ZipError::Io(e) => e.into(),
+        e => Error::Io(e.into()),
+    }
+}
@@ -0,0 +1,59 @@
+use std::path::{Path, PathBuf};
+
+use crate::error::Error;
+
+use super::{not_found, open_file, SourceFile, SqPackSource};
+
+//////////////////////////////////////////
+
+/// A `sqpack` directory on disk.
+#[derive(Debug, Clone)]
+pub struct DirectorySource {
+    path: PathBuf,
+}
+
+impl DirectorySource {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// The file a path refers to, unless it leaves the directory. The game directory above it
+    /// is only read through `open_game_file`.
+    fn file_path(&self, path: &str) -> Option<PathBuf> {
+        let escapes = path.split(['/', '\\']).any(|component| component == "..");
+        (!escapes).then(|| self.path.join(path))
+    }
+}
+
+impl SqPackSource for DirectorySource {
+    fn file_names(&self, folder: &str) -> Result<Vec<String>, Error> {
+        let folder_path = self.path.join(folder);
+        if !folder_path.is_dir() {
+            return Ok(Vec::new());
+        }
+
+        let mut file_names = Vec::new();
+        for dir_entry in std::fs::read_dir(folder_path)? {
+            if let Ok(file_name) = dir_entry?.file_name().into_string() {
+                file_names.push(file_name);
+            }
+        }
+        Ok(file_names)
+    }
+
+    fn open(&self, path: &str) -> Result<SourceFile, Error> {
+        let file_path = self.file_path(path).ok_or_else(|| not_found(path))?;
+        open_file(&file_path, path)
+    }
+
+    fn contains(&self, path: &str) -> bool {
+        self.file_path(path)
+            .is_some_and(|file_path| file_path.is_file())
+    }
+
+    fn directory(&self) -> Option<&Path> {
+        Some(&self.path)
+    }
+}
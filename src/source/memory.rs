@@ -0,0 +1,101 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use crate::error::Error;
+
+use super::{files_in_folder, normalize_path, not_found, SourceFile, SqPackSource};
+
+//////////////////////////////////////////
+
+/// A `sqpack` directory held in memory, e.g. a small fixture or an unpacked snapshot.
+#[derive(Debug, Clone, Default)]
+pub struct MemorySource {
+    files: BTreeMap<String, Arc<[u8]>>,
+    /// Files of the game directory above the `sqpack` directory, by name.
+    game_files: BTreeMap<String, Arc<[u8]>>,
+}
+
+impl MemorySource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds (or replaces) a file, given its path relative to the `sqpack` directory.
+    pub fn insert(&mut self, path: impl AsRef<str>, contents: impl Into<Arc<[u8]>>) {
+        self.files
+            .insert(normalize_path(path.as_ref()), contents.into());
+    }
+
+    /// Adds (or replaces) a file of the game directory, e.g. `ffxivgame.ver`.
+    pub fn insert_game_file(&mut self, file_name: impl AsRef<str>, contents: impl Into<Arc<[u8]>>) {
+        self.game_files
+            .insert(file_name.as_ref().to_string(), contents.into());
+    }
+
+    /// Reads every file of a tar archive of a `sqpack` directory. The archive may hold the
+    /// directory itself or any folder above it, e.g. the game's; compressed archives can be
+    /// read through a decoder such as `flate2::read::GzDecoder`.
+    #[cfg(feature = "tar")]
+    pub fn from_tar(reader: impl std::io::Read) -> Result<Self, Error> {
+        use std::io::Read;
+
+        use super::ArchivePath;
+
+        let mut files = Vec::new();
+        let mut archive = tar::Archive::new(reader);
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            let name = normalize_path(&entry.path()?.to_string_lossy());
+            // The size in the entry's header isn't trusted, so the buffer grows as it's read
+            let mut contents = Vec::new();
+            entry.read_to_end(&mut contents)?;
+            files.push((name, contents));
+        }
+
+        let root = super::sqpack_root(files.iter().map(|(name, _)| name.as_str()))
+            .ok_or_else(|| Error::PathNotFound("ffxiv".to_string()))?;
+        let mut source = Self::new();
+        for (name, contents) in files {
+            match super::relative_path(&root, &name) {
+                Some(ArchivePath::SqPack(path)) => source.insert(path, contents),
+                Some(ArchivePath::Game(file_name)) => source.insert_game_file(file_name, contents),
+                None => {}
+            }
+        }
+        Ok(source)
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+}
+
+impl SqPackSource for MemorySource {
+    fn file_names(&self, folder: &str) -> Result<Vec<String>, Error> {
+        Ok(files_in_folder(&self.files, folder))
+    }
+
+    fn open(&self, path: &str) -> Result<SourceFile, Error> {
+        self.files
+            .get(path)
+            .map(|contents| SourceFile::Bytes(contents.clone()))
+            .ok_or_else(|| not_found(path))
+    }
+
+    fn contains(&self, path: &str) -> bool {
+        self.files.contains_key(path)
+    }
+
+    fn open_game_file(&self, file_name: &str) -> Result<SourceFile, Error> {
+        self.game_files
+            .get(file_name)
+            .map(|contents| SourceFile::Bytes(contents.clone()))
+            .ok_or_else(|| not_found(file_name))
+    }
+}
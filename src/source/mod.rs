@@ -0,0 +1,195 @@
+#[cfg(feature = "zip")]
+mod archive;
+mod directory;
+mod memory;
+
+use std::{collections::BTreeMap, fs::File, path::Path, sync::Arc};
+
+use crate::error::Error;
+
+#[cfg(feature = "zip")]
+pub use archive::ZipSource;
+pub use directory::DirectorySource;
+pub use memory::MemorySource;
+
+//////////////////////////////////////////
+
+/// Where `FfxivLibrary` reads the files of a `sqpack` directory from. Paths are relative to
+/// that directory & use `/`, e.g. `ffxiv/0a0000.win32.index` or `ex1/ex1.ver`. Files of the
+/// game directory above it, like `ffxivgame.ver`, are read through `open_game_file`.
+pub trait SqPackSource: Send + Sync {
+    /// Names of the files directly inside a folder, e.g. `ffxiv`. A folder which doesn't exist
+    /// has none.
+    fn file_names(&self, folder: &str) -> Result<Vec<String>, Error>;
+
+    /// Fails with `Error::PathNotFound` if the file doesn't exist.
+    fn open(&self, path: &str) -> Result<SourceFile, Error>;
+
+    fn contains(&self, path: &str) -> bool;
+
+    /// Opens a file directly inside the game directory which holds the `sqpack` directory,
+    /// e.g. `ffxivgame.ver`. By default, it's looked for next to `directory`.
+    fn open_game_file(&self, file_name: &str) -> Result<SourceFile, Error> {
+        let game_path = self
+            .directory()
+            .and_then(Path::parent)
+            .filter(|_| is_file_name(file_name))
+            .ok_or_else(|| not_found(file_name))?;
+        open_file(&game_path.join(file_name), file_name)
+    }
+
+    /// The directory on disk, for sources which read from one. Only those can be changed
+    /// through `FfxivLibrary::replace_file`.
+    fn directory(&self) -> Option<&Path> {
+        None
+    }
+}
+
+/// A file opened by a `SqPackSource`.
+#[derive(Debug)]
+pub enum SourceFile {
+    /// A file on disk, which the library reads with positional reads or maps into memory.
+    File(File),
+    /// A file which is already in memory, whose blocks are inflated straight from its bytes.
+    Bytes(Arc<[u8]>),
+}
+
+//////////////////////////////////////////
+
+pub(crate) fn not_found(path: &str) -> Error {
+    Error::PathNotFound(path.to_string())
+}
+
+/// Opens a file on disk, reporting it as `path` if it doesn't exist.
+pub(crate) fn open_file(file_path: &Path, path: &str) -> Result<SourceFile, Error> {
+    match File::open(file_path) {
+        Ok(file) => Ok(SourceFile::File(file)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(not_found(path)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Whether a path names a file of its folder, rather than one in another folder.
+pub(crate) fn is_file_name(path: &str) -> bool {
+    !path.is_empty() && path != "." && path != ".." && !path.contains(['/', '\\'])
+}
+
+/// Names of the files directly inside `folder`, out of paths sorted by `BTreeMap`.
+pub(crate) fn files_in_folder<V>(files: &BTreeMap<String, V>, folder: &str) -> Vec<String> {
+    let prefix = format!("{}/", folder.trim_end_matches('/'));
+    files
+        .range(prefix.clone()..)
+        .map(|(path, _)| path)
+        .take_while(|path| path.starts_with(&prefix))
+        .map(|path| &path[prefix.len()..])
+        .filter(|file_name| !file_name.contains('/'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Turns `\` into `/` & drops any leading `/` or `./`.
+pub(crate) fn normalize_path(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let mut path = path.as_str();
+    loop {
+        if let Some(rest) = path.strip_prefix("./") {
+            path = rest;
+        } else if let Some(rest) = path.strip_prefix('/') {
+            path = rest;
+        } else {
+            return path.to_string();
+        }
+    }
+}
+
+/// The folder of an archive which holds the `sqpack` folders, e.g. `""` or `game/sqpack/`. It's
+/// found from the base game's indexes, `ffxiv/*.index` & `ffxiv/*.index2`, as other folders like
+/// `game/movie/ffxiv/` share the name.
+#[cfg(any(feature = "tar", feature = "zip"))]
+pub(crate) fn sqpack_root<'a>(names: impl IntoIterator<Item = &'a str>) -> Option<String> {
+    names
+        .into_iter()
+        .filter(|name| name.ends_with(".index") || name.ends_with(".index2"))
+        .filter_map(|name| {
+            let (folder, _) = name.rsplit_once('/')?;
+            let root = folder.strip_suffix("ffxiv")?;
+            (root.is_empty() || root.ends_with('/')).then(|| root.to_string())
+        })
+        .min_by_key(|root| root.len())
+}
+
+/// Where an archive entry belongs, relative to the `sqpack` folder.
+#[cfg(any(feature = "tar", feature = "zip"))]
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum ArchivePath {
+    /// A file below the `sqpack` folder.
+    SqPack(String),
+    /// A file of the game folder above it, like `ffxivgame.ver`.
+    Game(String),
+}
+
+/// Path of an archive entry relative to `root`. Files directly in the folder above it are
+/// kept as game files, anything else is left out.
+#[cfg(any(feature = "tar", feature = "zip"))]
+pub(crate) fn relative_path(root: &str, name: &str) -> Option<ArchivePath> {
+    if let Some(path) = name.strip_prefix(root) {
+        return (!path.is_empty() && !path.ends_with('/'))
+            .then(|| ArchivePath::SqPack(path.to_string()));
+    }
+
+    let parent = root
+        .trim_end_matches('/')
+        .rsplit_once('/')
+        .map_or("", |(parent, _)| parent);
+    let parent = if parent.is_empty() {
+        String::new()
+    } else {
+        format!("{}/", parent)
+    };
+    let file_name = name.strip_prefix(&parent)?;
+    is_file_name(file_name).then(|| ArchivePath::Game(file_name.to_string()))
+}
+
+//////////////////////////////////////////
+
+#[cfg(all(test, any(feature = "tar", feature = "zip")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sqpack_root_of_sqpack_directory() {
+        let names = ["ffxiv/0a0000.win32.index", "ffxiv/0a0000.win32.dat0"];
+        assert_eq!(sqpack_root(names), Some(String::new()));
+    }
+
+    #[test]
+    fn sqpack_root_skips_movie_folder() {
+        let names = [
+            "game/ffxivgame.ver",
+            "game/movie/ffxiv/00000.bk2",
+            "game/sqpack/ffxiv/0a0000.win32.dat0",
+            "game/sqpack/ffxiv/0a0000.win32.index",
+            "game/sqpack/ffxiv/0a0000.win32.index2",
+            "game/sqpack/ex1/020100.win32.index",
+        ];
+        assert_eq!(sqpack_root(names), Some("game/sqpack/".to_string()));
+        assert_eq!(
+            relative_path("game/sqpack/", "game/sqpack/ffxiv/0a0000.win32.index"),
+            Some(ArchivePath::SqPack("ffxiv/0a0000.win32.index".to_string()))
+        );
+        assert_eq!(
+            relative_path("game/sqpack/", "game/ffxivgame.ver"),
+            Some(ArchivePath::Game("ffxivgame.ver".to_string()))
+        );
+        assert_eq!(
+            relative_path("game/sqpack/", "game/movie/ffxiv/00000.bk2"),
+            None
+        );
+    }
+
+    #[test]
+    fn sqpack_root_needs_base_game_index() {
+        let names = ["game/movie/ffxiv/00000.bk2", "notffxiv/0a0000.win32.index"];
+        assert_eq!(sqpack_root(names), None);
+    }
+}
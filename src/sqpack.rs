@@ -1,178 +1,643 @@
 use std::{
     collections::HashMap,
-    error::Error,
     fmt::Debug,
     fs::File,
-    io::{BufReader, Seek, SeekFrom},
+    io::{BufReader, Cursor, Seek, SeekFrom},
     path::Path,
 };
 
-use byteorder::{LittleEndian, ReadBytesExt};
+use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt};
+
+use crate::{error::Error, mapped_file::map_file};
 
 ///////////////////////////////////////////////
 
-pub struct SqPackIndexFile(HashMap<u64, SqPackIndexTableEntry>);
+pub struct SqPackIndexFile {
+    headers: [SqPackIndexHeaders; 2],
+    entries: HashMap<SqPackIndexHash, SqPackIndexTableEntry>,
+    collisions: HashMap<String, SqPackIndexTableEntry>,
+}
 
 impl SqPackIndexFile {
-    pub fn from_file(file_path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+    pub fn from_file(file_path: impl AsRef<Path>) -> Result<Self, Error> {
+        let file_path = file_path.as_ref();
+        let mut reader = BufReader::new(File::open(file_path)?);
+        let index1 = Self::from_reader1(&mut reader)
+            .map_err(|e| e.with_path(file_path.to_string_lossy()))?;
+
+        let mut file_path = file_path.as_os_str().to_owned();
+        file_path.push("2");
         let mut reader = BufReader::new(File::open(&file_path)?);
-        let index1 = Self::from_reader1(&mut reader)?;
+        let index2 = Self::from_reader2(&mut reader)
+            .map_err(|e| e.with_path(file_path.to_string_lossy()))?;
 
-        let file_path = format!("{}2", file_path.as_ref().to_str().unwrap());
-        let mut reader = BufReader::new(File::open(&file_path)?);
-        let index2 = Self::from_reader2(&mut reader)?;
+        Ok(Self::merge(index1, index2))
+    }
 
-        let mut entries = index2.0;
-        for entry in index1.0 {
-            entries.insert(entry.0, entry.1);
-        }
+    /// Like `from_file`, but parses both indexes straight from memory-mapped bytes.
+    pub fn from_mapped_file(file_path: impl AsRef<Path>) -> Result<Self, Error> {
+        let file_path = file_path.as_ref();
+        let index1 = Self::from_reader1(&mut Cursor::new(&map_file(file_path)?[..]))
+            .map_err(|e| e.with_path(file_path.to_string_lossy()))?;
 
-        Ok(Self(entries))
+        let mut file_path = file_path.as_os_str().to_owned();
+        file_path.push("2");
+        let index2 = Self::from_reader2(&mut Cursor::new(&map_file(&file_path)?[..]))
+            .map_err(|e| e.with_path(file_path.to_string_lossy()))?;
+
+        Ok(Self::merge(index1, index2))
     }
 
-    fn from_reader_common<R: ReadBytesExt + Seek>(reader: &mut R) -> Result<u32, Box<dyn Error>> {
-        reader.seek(SeekFrom::Start(0))?;
-        let header = SqPackHeader::from_reader(reader)?;
+    pub(crate) fn merge(index1: IndexData, index2: IndexData) -> Self {
+        let mut entries = index2.entries;
+        entries.extend(index1.entries);
+        let mut collisions = index2.collisions;
+        collisions.extend(index1.collisions);
 
-        reader.seek(SeekFrom::Start(header.size.into()))?;
-        let index_header = SqPackIndexHeader::from_reader(reader)?;
+        Self {
+            headers: [index1.headers, index2.headers],
+            entries,
+            collisions,
+        }
+    }
 
-        reader.seek(SeekFrom::Start(index_header.index_data_offset.into()))?;
-        Ok(index_header.index_data_size)
+    pub(crate) fn from_reader1<R: ReadBytesExt + Seek>(reader: &mut R) -> Result<IndexData, Error> {
+        let headers = SqPackIndexHeaders::from_reader(reader)?;
+        if headers.sqpack.platform_id.is_big_endian() {
+            Self::read_tables1::<BigEndian, R>(reader, headers)
+        } else {
+            Self::read_tables1::<LittleEndian, R>(reader, headers)
+        }
     }
 
-    fn from_reader1<R: ReadBytesExt + Seek>(reader: &mut R) -> Result<Self, Box<dyn Error>> {
-        let data_size = Self::from_reader_common(reader)?;
-        let num_entries = data_size / 16; // Two 64-bit values per table entry
+    fn read_tables1<E: ByteOrder, R: ReadBytesExt + Seek>(
+        reader: &mut R,
+        headers: SqPackIndexHeaders,
+    ) -> Result<IndexData, Error> {
+        let index_header = &headers.index;
+        let platform = headers.sqpack.platform_id;
+
+        reader.seek(SeekFrom::Start(index_header.files.offset.into()))?;
+        let num_entries = index_header.files.size / 16; // Two 64-bit values per table entry
         let mut entries = HashMap::new();
         for _ in 0..num_entries {
-            let entry = SqPackIndexTableEntry::from_reader1(reader)?;
-            entries.insert(entry.hash, entry);
+            let entry = SqPackIndexTableEntry::from_reader1::<E, R>(reader, platform)?;
+            if !entry.is_synonym {
+                entries.insert(entry.hash, entry);
+            }
         }
 
-        Ok(Self(entries))
+        reader.seek(SeekFrom::Start(index_header.synonyms.offset.into()))?;
+        let num_collisions = index_header.synonyms.size / SYNONYM_ENTRY_SIZE;
+        let collisions = (0..num_collisions)
+            .map(|_| SqPackIndexTableEntry::from_synonym_reader1::<E, R>(reader, platform))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(IndexData {
+            headers,
+            entries,
+            collisions: collisions.into_iter().flatten().collect(),
+        })
     }
 
-    fn from_reader2<R: ReadBytesExt + Seek>(reader: &mut R) -> Result<Self, Box<dyn Error>> {
-        let data_size = Self::from_reader_common(reader)?;
-        let num_entries = data_size / 8; // Two 32-bit values per table entry
+    pub(crate) fn from_reader2<R: ReadBytesExt + Seek>(reader: &mut R) -> Result<IndexData, Error> {
+        let headers = SqPackIndexHeaders::from_reader(reader)?;
+        if headers.sqpack.platform_id.is_big_endian() {
+            Self::read_tables2::<BigEndian, R>(reader, headers)
+        } else {
+            Self::read_tables2::<LittleEndian, R>(reader, headers)
+        }
+    }
+
+    fn read_tables2<E: ByteOrder, R: ReadBytesExt + Seek>(
+        reader: &mut R,
+        headers: SqPackIndexHeaders,
+    ) -> Result<IndexData, Error> {
+        let index_header = &headers.index;
+        let platform = headers.sqpack.platform_id;
+
+        reader.seek(SeekFrom::Start(index_header.files.offset.into()))?;
+        let num_entries = index_header.files.size / 8; // Two 32-bit values per table entry
         let mut entries = HashMap::new();
         for _ in 0..num_entries {
-            let entry = SqPackIndexTableEntry::from_reader2(reader)?;
-            entries.insert(entry.hash, entry);
+            let entry = SqPackIndexTableEntry::from_reader2::<E, R>(reader, platform)?;
+            if !entry.is_synonym {
+                entries.insert(entry.hash, entry);
+            }
         }
 
-        Ok(Self(entries))
+        reader.seek(SeekFrom::Start(index_header.synonyms.offset.into()))?;
+        let num_collisions = index_header.synonyms.size / SYNONYM_ENTRY_SIZE;
+        let collisions = (0..num_collisions)
+            .map(|_| SqPackIndexTableEntry::from_synonym_reader2::<E, R>(reader, platform))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(IndexData {
+            headers,
+            entries,
+            collisions: collisions.into_iter().flatten().collect(),
+        })
     }
 
     pub fn entry_from_path(&self, path: impl AsRef<str>) -> Option<&SqPackIndexTableEntry> {
+        let path = path.as_ref();
+        if let Some(entry) = self.collisions.get(&path.to_lowercase()) {
+            return Some(entry);
+        }
+
+        let (hash1, hash2) = SqPackIndexHash::from_path(path)?;
+        self.entries
+            .get(&hash1)
+            .or_else(|| self.entries.get(&hash2))
+    }
+
+    /// Headers of the `.index` file.
+    pub fn index1_headers(&self) -> &SqPackIndexHeaders {
+        &self.headers[0]
+    }
+
+    /// Headers of the `.index2` file.
+    pub fn index2_headers(&self) -> &SqPackIndexHeaders {
+        &self.headers[1]
+    }
+
+    /// Every entry of both the `.index` & `.index2` files, in no particular order.
+    pub fn entries(&self) -> impl Iterator<Item = &SqPackIndexTableEntry> {
+        self.entries.values().chain(self.collisions.values())
+    }
+
+    /// Full paths of the files whose hashes collide with another file's.
+    pub fn collision_paths(&self) -> impl Iterator<Item = &str> {
+        self.collisions.keys().map(|path| path.as_str())
+    }
+
+    /// Entries which can only be found by their hash, i.e. excluding the colliding ones.
+    pub(crate) fn hashed_entries(&self) -> impl Iterator<Item = &SqPackIndexTableEntry> {
+        self.entries.values()
+    }
+
+    /// Colliding entries, along with their full paths.
+    pub(crate) fn collisions(&self) -> impl Iterator<Item = (&str, &SqPackIndexTableEntry)> {
+        self.collisions
+            .iter()
+            .map(|(path, entry)| (path.as_str(), entry))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len() + self.collisions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// The parsed contents of a single `.index` or `.index2` file.
+pub(crate) struct IndexData {
+    pub headers: SqPackIndexHeaders,
+    pub entries: HashMap<SqPackIndexHash, SqPackIndexTableEntry>,
+    pub collisions: HashMap<String, SqPackIndexTableEntry>,
+}
+
+///////////////////////////////////////////////
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SqPackIndexHash {
+    /// `.index` hash, made of the separate folder & file name hashes.
+    FolderFile { folder: u32, file: u32 },
+    /// `.index2` hash, of the full path.
+    FullPath(u32),
+}
+
+impl SqPackIndexHash {
+    /// Returns the `.index` & `.index2` hashes for a path.
+    pub fn from_path(path: impl AsRef<str>) -> Option<(Self, Self)> {
         let resource = Resource::new(path)?;
-        let hash1 = ((resource.directory.hash as u64) << 32) | (resource.file.hash as u64);
-        let hash2 = resource.full_hash.hash as u64;
-        self.0.get(&hash1).or_else(|| self.0.get(&hash2))
+        Some((
+            SqPackIndexHash::FolderFile {
+                folder: resource.directory.hash,
+                file: resource.file.hash,
+            },
+            SqPackIndexHash::FullPath(resource.full_hash.hash),
+        ))
+    }
+}
+
+impl Debug for SqPackIndexHash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            SqPackIndexHash::FolderFile { folder, file } => {
+                write!(f, "{:08X}_{:08X}", folder, file)
+            }
+            SqPackIndexHash::FullPath(hash) => write!(f, "{:08X}", hash),
+        }
     }
 }
 
 ///////////////////////////////////////////////
 
+#[derive(Clone, Copy)]
 pub struct SqPackIndexTableEntry {
-    pub hash: u64,
+    pub hash: SqPackIndexHash,
     pub data_file_id: u32,
     pub offset: u32,
+    /// Set when the hash is shared with other files, whose entries are in the synonym table.
+    pub is_synonym: bool,
+    /// Platform of the index this entry came from, which decides the layout of its data.
+    pub platform: PlatformId,
 }
 
-impl SqPackIndexTableEntry {
-    fn from_reader1<R: ReadBytesExt>(reader: &mut R) -> Result<Self, Box<dyn Error>> {
-        let hash = reader.read_u64::<LittleEndian>()?;
-        let mut data = reader.read_u64::<LittleEndian>()?;
+/// Each synonym table entry is padded out to 256 bytes, most of which is the path.
+const SYNONYM_ENTRY_SIZE: u32 = 0x100;
+const SYNONYM_PATH_SIZE: usize = 0xF0;
 
-        data >>= 1;
-        let data_file_id = (data & 0b111) as u32;
-        let offset = ((data & !0x7) as u32) << 4;
+impl SqPackIndexTableEntry {
+    fn new(hash: SqPackIndexHash, data: u32, platform: PlatformId) -> Self {
+        let is_synonym = (data & 0b1) != 0;
+        let data = data >> 1;
+        let data_file_id = data & 0b111;
+        let offset = (data & !0x7) << 4;
 
-        Ok(Self {
+        Self {
             hash,
             data_file_id,
             offset,
-        })
+            is_synonym,
+            platform,
+        }
     }
 
-    fn from_reader2<R: ReadBytesExt>(reader: &mut R) -> Result<Self, Box<dyn Error>> {
-        let hash = reader.read_u32::<LittleEndian>()?;
-        let mut data = reader.read_u32::<LittleEndian>()?;
+    /// The reverse of `new`: packs a location into the data field of an index table entry.
+    /// Offsets must be 128-byte aligned, and fit in the 32 bits `new` decodes them to.
+    pub(crate) fn encode_data(data_file_id: u32, offset: u64) -> Option<u32> {
+        if data_file_id > 0b111 || offset & 0x7F != 0 || offset > u32::MAX as u64 {
+            return None;
+        }
+        let data = (offset >> 4) | data_file_id as u64;
+        u32::try_from(data << 1).ok()
+    }
 
-        data >>= 1;
-        let data_file_id = data & 0b1110;
-        let offset = (data & !0x7) << 4;
+    fn from_reader1<E: ByteOrder, R: ReadBytesExt>(
+        reader: &mut R,
+        platform: PlatformId,
+    ) -> Result<Self, Error> {
+        let file = reader.read_u32::<E>()?;
+        let folder = reader.read_u32::<E>()?;
+        let data = reader.read_u32::<E>()?;
+        let _padding = reader.read_u32::<E>()?;
+
+        Ok(Self::new(
+            SqPackIndexHash::FolderFile { folder, file },
+            data,
+            platform,
+        ))
+    }
 
-        Ok(Self {
-            hash: hash as u64,
-            data_file_id,
-            offset,
-        })
+    fn from_reader2<E: ByteOrder, R: ReadBytesExt>(
+        reader: &mut R,
+        platform: PlatformId,
+    ) -> Result<Self, Error> {
+        let hash = reader.read_u32::<E>()?;
+        let data = reader.read_u32::<E>()?;
+
+        Ok(Self::new(SqPackIndexHash::FullPath(hash), data, platform))
+    }
+
+    fn from_synonym_reader1<E: ByteOrder, R: ReadBytesExt>(
+        reader: &mut R,
+        platform: PlatformId,
+    ) -> Result<Option<(String, Self)>, Error> {
+        let file = reader.read_u32::<E>()?;
+        let folder = reader.read_u32::<E>()?;
+        let data = reader.read_u32::<E>()?;
+        let _index = reader.read_u32::<E>()?;
+        let path = read_synonym_path(reader)?;
+
+        let entry = Self::new(SqPackIndexHash::FolderFile { folder, file }, data, platform);
+        Ok(path.map(|path| (path, entry)))
+    }
+
+    fn from_synonym_reader2<E: ByteOrder, R: ReadBytesExt>(
+        reader: &mut R,
+        platform: PlatformId,
+    ) -> Result<Option<(String, Self)>, Error> {
+        let hash = reader.read_u32::<E>()?;
+        let _unknown = reader.read_u32::<E>()?;
+        let data = reader.read_u32::<E>()?;
+        let _index = reader.read_u32::<E>()?;
+        let path = read_synonym_path(reader)?;
+
+        let entry = Self::new(SqPackIndexHash::FullPath(hash), data, platform);
+        Ok(path.map(|path| (path, entry)))
+    }
+}
+
+/// Reads the NUL-padded path of a synonym entry. The table ends with a blank entry.
+fn read_synonym_path<R: ReadBytesExt>(reader: &mut R) -> Result<Option<String>, Error> {
+    let mut path = [0; SYNONYM_PATH_SIZE];
+    reader.read_exact(&mut path)?;
+
+    let length = path.iter().position(|&c| c == 0).unwrap_or(path.len());
+    if length == 0 {
+        return Ok(None);
     }
+    Ok(Some(
+        String::from_utf8(path[..length].to_vec())?.to_lowercase(),
+    ))
 }
 
 impl Debug for SqPackIndexTableEntry {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("SqPackIndexTableEntry")
-            .field("hash", &format!("{:016X}", self.hash))
+            .field("hash", &self.hash)
             .field("data_file_id", &self.data_file_id)
             .field("offset", &format!("{:08X}", self.offset))
+            .field("is_synonym", &self.is_synonym)
+            .field("platform", &self.platform)
             .finish()
     }
 }
 
 ///////////////////////////////////////////////
 
-struct SqPackIndexHeader {
-    pub index_data_offset: u32,
-    pub index_data_size: u32,
+/// Headers of a single `.index` or `.index2` file.
+#[derive(Debug, Clone)]
+pub struct SqPackIndexHeaders {
+    pub sqpack: SqPackHeader,
+    pub index: SqPackIndexHeader,
+}
+
+impl SqPackIndexHeaders {
+    pub fn from_reader<R: ReadBytesExt + Seek>(reader: &mut R) -> Result<Self, Error> {
+        reader.seek(SeekFrom::Start(0))?;
+        let sqpack = SqPackHeader::from_reader(reader)?;
+
+        reader.seek(SeekFrom::Start(sqpack.size.into()))?;
+        let index = if sqpack.platform_id.is_big_endian() {
+            SqPackIndexHeader::from_reader::<BigEndian, R>(reader)?
+        } else {
+            SqPackIndexHeader::from_reader::<LittleEndian, R>(reader)?
+        };
+
+        Ok(Self { sqpack, index })
+    }
 }
 
+///////////////////////////////////////////////
+
+#[derive(Debug, Clone)]
+pub struct SqPackIndexHeader {
+    pub size: u32,
+    pub version: u32,
+    /// The file (hash) table.
+    pub files: SqPackIndexSegment,
+    pub data_file_count: u32,
+    /// The hash collision table.
+    pub synonyms: SqPackIndexSegment,
+    pub empty_blocks: SqPackIndexSegment,
+    /// The folder table, only present in `.index` files.
+    pub folders: SqPackIndexSegment,
+    pub index_type: u32,
+    /// SHA-1 of this header, up to the digest itself.
+    pub digest: [u8; 20],
+}
+
+/// Offset of the digest within the index header, which is also the number of bytes it covers.
+pub(crate) const INDEX_HEADER_DIGEST_OFFSET: u32 = 0x3C0;
+
 impl SqPackIndexHeader {
-    pub fn from_reader<R: ReadBytesExt>(reader: &mut R) -> Result<Self, Box<dyn Error>> {
-        let _size = reader.read_u32::<LittleEndian>()?;
-        let _ptype = reader.read_u32::<LittleEndian>()?;
-        let index_data_offset = reader.read_u32::<LittleEndian>()?;
-        let index_data_size = reader.read_u32::<LittleEndian>()?;
+    pub fn from_reader<E: ByteOrder, R: ReadBytesExt>(reader: &mut R) -> Result<Self, Error> {
+        let size = reader.read_u32::<E>()?;
+        let version = reader.read_u32::<E>()?;
+        let files = SqPackIndexSegment::from_reader::<E, R>(reader)?;
+        let data_file_count = reader.read_u32::<E>()?;
+        let synonyms = SqPackIndexSegment::from_reader::<E, R>(reader)?;
+        let empty_blocks = SqPackIndexSegment::from_reader::<E, R>(reader)?;
+        let folders = SqPackIndexSegment::from_reader::<E, R>(reader)?;
+        let index_type = reader.read_u32::<E>()?;
+
+        // 4 segments of 72 bytes, plus 4 u32 values read so far
+        skip(reader, INDEX_HEADER_DIGEST_OFFSET - 4 * 72 - 4 * 4)?;
+        let digest = read_digest(reader)?;
+
+        Ok(Self {
+            size,
+            version,
+            files,
+            data_file_count,
+            synonyms,
+            empty_blocks,
+            folders,
+            index_type,
+            digest,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SqPackIndexSegment {
+    pub offset: u32,
+    pub size: u32,
+    /// SHA-1 of the segment's data.
+    pub digest: [u8; 20],
+}
+
+impl SqPackIndexSegment {
+    pub fn from_reader<E: ByteOrder, R: ReadBytesExt>(reader: &mut R) -> Result<Self, Error> {
+        let offset = reader.read_u32::<E>()?;
+        let size = reader.read_u32::<E>()?;
+        let digest = read_digest(reader)?;
+
+        Ok(Self {
+            offset,
+            size,
+            digest,
+        })
+    }
+}
+
+///////////////////////////////////////////////
+
+#[derive(Debug, Clone)]
+pub struct SqPackDataHeader {
+    pub size: u32,
+    pub version: u32,
+    /// Number of bytes of entries following the headers.
+    pub data_size: u32,
+    /// Number of `.datN` files the data is spread across.
+    pub data_file_count: u32,
+    pub max_file_size: u64,
+    /// SHA-1 of the entries, over `data_size` bytes.
+    pub data_digest: [u8; 20],
+    /// SHA-1 of this header, up to the digest itself.
+    pub digest: [u8; 20],
+}
+
+/// Offset of the digest within the data header, which is also the number of bytes it covers.
+pub(crate) const DATA_HEADER_DIGEST_OFFSET: u32 = 0x3C0;
+
+impl SqPackDataHeader {
+    pub fn from_reader<E: ByteOrder, R: ReadBytesExt>(reader: &mut R) -> Result<Self, Error> {
+        let size = reader.read_u32::<E>()?;
+        skip(reader, 4)?;
+        let version = reader.read_u32::<E>()?;
+        let data_size = reader.read_u32::<E>()?;
+        let data_file_count = reader.read_u32::<E>()?;
+        skip(reader, 4)?;
+        let max_file_size = reader.read_u64::<E>()?;
+        skip(reader, 8)?;
+        let data_digest = read_digest(reader)?;
+
+        // The data digest's 64-byte field, plus 40 bytes read before it
+        skip(reader, DATA_HEADER_DIGEST_OFFSET - 64 - 40)?;
+        let digest = read_digest(reader)?;
 
         Ok(Self {
-            index_data_offset,
-            index_data_size,
+            size,
+            version,
+            data_size,
+            data_file_count,
+            max_file_size,
+            data_digest,
+            digest,
         })
     }
 }
 
 ///////////////////////////////////////////////
 
-struct SqPackHeader {
+#[derive(Debug, Clone)]
+pub struct SqPackHeader {
+    pub platform_id: PlatformId,
     pub size: u32,
+    pub version: u32,
+    pub file_type: SqPackFileType,
+    /// SHA-1 of this header, up to the digest itself.
+    pub digest: [u8; 20],
 }
 
+/// `SqPack\0\0`, read as a little-endian u64.
+const SQPACK_MAGIC: u64 = 0x00006B6361507153;
+
+/// Offset of the digest within the SqPack header, which is also the number of bytes it covers.
+pub(crate) const SQPACK_HEADER_DIGEST_OFFSET: u32 = 0x3C0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum PlatformId {
     Win32,
     PS3,
     PS4,
+    PS5,
 }
 
-impl SqPackHeader {
-    pub fn from_reader<R: ReadBytesExt>(reader: &mut R) -> Result<Self, Box<dyn Error>> {
-        let _magic = reader.read_u64::<LittleEndian>()?;
-        let platform_id = reader.read_u32::<LittleEndian>()?;
-        let size = reader.read_u32::<LittleEndian>()?;
-        let _version = reader.read_u32::<LittleEndian>()?;
-        let _ptype = reader.read_u32::<LittleEndian>()?;
-
-        let _platform_id = match platform_id {
+impl PlatformId {
+    pub const ALL: [PlatformId; 4] = [
+        PlatformId::Win32,
+        PlatformId::PS3,
+        PlatformId::PS4,
+        PlatformId::PS5,
+    ];
+
+    /// The platform part of SqPack file names, e.g. `0a0000.win32.index`.
+    pub fn file_extension(&self) -> &'static str {
+        match *self {
+            PlatformId::Win32 => "win32",
+            PlatformId::PS3 => "ps3",
+            PlatformId::PS4 => "ps4",
+            PlatformId::PS5 => "ps5",
+        }
+    }
+
+    pub fn is_big_endian(&self) -> bool {
+        matches!(*self, PlatformId::PS3)
+    }
+}
+
+impl TryFrom<u8> for PlatformId {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
             0 => PlatformId::Win32,
             1 => PlatformId::PS3,
             2 => PlatformId::PS4,
-            _ => panic!("Invalid PlatformID read from SqPack file"),
+            3 => PlatformId::PS5,
+            _ => return Err(Error::unknown_value("PlatformId", value)),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SqPackFileType {
+    SqDatabase,
+    Data,
+    Index,
+}
+
+impl SqPackHeader {
+    pub fn from_reader<R: ReadBytesExt>(reader: &mut R) -> Result<Self, Error> {
+        let magic = reader.read_u64::<LittleEndian>()?;
+        if magic != SQPACK_MAGIC {
+            return Err(Error::InvalidMagic {
+                kind: "SqPack",
+                path: None,
+            });
+        }
+
+        // A single byte, so it can be read before knowing the platform's endianness
+        let platform_id = reader.read_u8()?;
+        skip(reader, 3)?;
+        let platform_id = PlatformId::try_from(platform_id)?;
+
+        if platform_id.is_big_endian() {
+            Self::from_reader_rest::<BigEndian, R>(reader, platform_id)
+        } else {
+            Self::from_reader_rest::<LittleEndian, R>(reader, platform_id)
+        }
+    }
+
+    fn from_reader_rest<E: ByteOrder, R: ReadBytesExt>(
+        reader: &mut R,
+        platform_id: PlatformId,
+    ) -> Result<Self, Error> {
+        let size = reader.read_u32::<E>()?;
+        let version = reader.read_u32::<E>()?;
+        let file_type = reader.read_u32::<E>()?;
+
+        // The magic, plus 4 u32 values read so far
+        skip(reader, SQPACK_HEADER_DIGEST_OFFSET - 8 - 4 * 4)?;
+        let digest = read_digest(reader)?;
+
+        let file_type = match file_type {
+            0 => SqPackFileType::SqDatabase,
+            1 => SqPackFileType::Data,
+            2 => SqPackFileType::Index,
+            _ => return Err(Error::unknown_value("SqPackFileType", file_type)),
         };
 
-        Ok(Self { size })
+        Ok(Self {
+            platform_id,
+            size,
+            version,
+            file_type,
+            digest,
+        })
+    }
+}
+
+/// Digests are stored in 64-byte fields, of which SHA-1 only uses the first 20.
+fn read_digest<R: ReadBytesExt>(reader: &mut R) -> Result<[u8; 20], Error> {
+    let mut digest = [0; 64];
+    reader.read_exact(&mut digest)?;
+    Ok(digest[..20].try_into().unwrap())
+}
+
+fn skip<R: ReadBytesExt>(reader: &mut R, count: u32) -> Result<(), Error> {
+    let mut skipped = <&mut R as std::io::Read>::take(reader, count as u64);
+    if std::io::copy(&mut skipped, &mut std::io::sink())? != count as u64 {
+        return Err(Error::Truncated { path: None });
     }
+    Ok(())
 }
 
 ///////////////////////////////////////////////
@@ -237,3 +702,55 @@ fn lower_case_string_bytes(string: &str) -> Box<[u8]> {
         .collect::<Vec<_>>()
         .into_boxed_slice()
 }
+
+///////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ffxiv_library::FfxivLibrary, sqpack_writer::SqPackWriter};
+
+    /// File names of the same length whose CRCs match, so the paths collide in both indexes.
+    const COLLIDING_PATHS: [&str; 2] = ["exd/0008ddf9.exd", "exd/02008894.exd"];
+
+    #[test]
+    fn colliding_paths_resolve_to_their_own_entries() {
+        let [path1, path2] = COLLIDING_PATHS;
+        assert_eq!(
+            SqPackIndexHash::from_path(path1),
+            SqPackIndexHash::from_path(path2)
+        );
+
+        for platform in PlatformId::ALL {
+            let dir = tempfile::tempdir().unwrap();
+            let mut writer = SqPackWriter::new(platform);
+            writer.add_file(path1, vec![1; 100]).unwrap();
+            writer.add_file(path2, vec![2; 200]).unwrap();
+            writer.add_file("exd/root.exl", vec![3; 50]).unwrap();
+            writer.write(dir.path()).unwrap();
+
+            let index_path = dir
+                .path()
+                .join(format!("ffxiv/0a0000.{}.index", platform.file_extension()));
+            let index = SqPackIndexFile::from_file(index_path).unwrap();
+            let entry1 = index.entry_from_path(path1).unwrap();
+            let entry2 = index.entry_from_path(path2).unwrap();
+            assert_ne!(entry1.offset, entry2.offset);
+            // Lookups don't depend on the path's case
+            let upper_case = path2.to_uppercase();
+            assert_eq!(
+                index.entry_from_path(upper_case).unwrap().offset,
+                entry2.offset
+            );
+
+            let mut collision_paths = index.collision_paths().collect::<Vec<_>>();
+            collision_paths.sort();
+            assert_eq!(collision_paths, COLLIDING_PATHS);
+            assert!(index.entry_from_path("exd/root.exl").is_some());
+
+            let library = FfxivLibrary::with_platform(dir.path(), platform);
+            assert_eq!(&library.get_file(path1).unwrap()[..], [1; 100]);
+            assert_eq!(&library.get_file(path2).unwrap()[..], [2; 200]);
+        }
+    }
+}
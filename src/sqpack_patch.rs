@@ -0,0 +1,255 @@
+use std::{
+    fs::OpenOptions,
+    io::{Cursor, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use byteorder::{ByteOrder, ReadBytesExt};
+
+use crate::{
+    error::Error,
+    journal::Journal,
+    sqpack::{
+        PlatformId, SqPackIndexHash, SqPackIndexHeaders, SqPackIndexTableEntry,
+        INDEX_HEADER_DIGEST_OFFSET,
+    },
+    sqpack_writer::{
+        encode_entry, with_header_digest, DataFileWriter, ALIGNMENT, DEFAULT_MAX_DAT_SIZE,
+        FIRST_ENTRY_OFFSET, HEADER_SIZE, MAX_DATA_FILES,
+    },
+};
+
+//////////////////////////////////////////
+
+/// Offsets within the index header of each segment (files, synonyms, empty blocks, folders),
+/// and of the data file count.
+const SEGMENT_FIELD_OFFSETS: [usize; 4] = [0x08, 0x54, 0x9C, 0xE4];
+const DATA_FILE_COUNT_OFFSET: usize = 0x50;
+
+/// Offsets within the data header of the data size & data digest.
+const DATA_SIZE_OFFSET: usize = 0x0C;
+const DATA_DIGEST_OFFSET: usize = 0x28;
+
+const SYNONYM_ENTRY_SIZE: usize = 0x100;
+const SYNONYM_PATH_OFFSET: usize = 0x10;
+
+//////////////////////////////////////////
+
+/// Replaces the data of an existing file: the new entry is appended to the last `.datN`
+/// (or a new one, once that's full), and the file's entries in the `.index` & `.index2` are
+/// pointed at it. The old data is left in place.
+pub(crate) fn replace_entry<E: ByteOrder>(
+    journal: &mut Journal,
+    base_path: &Path,
+    platform: PlatformId,
+    path: &str,
+    contents: &[u8],
+) -> Result<(), Error> {
+    let index_paths = [".index", ".index2"].map(|extension| {
+        let mut index_path = base_path.as_os_str().to_owned();
+        index_path.push(extension);
+        PathBuf::from(index_path)
+    });
+
+    let index = std::fs::read(&index_paths[0])?;
+    let headers = SqPackIndexHeaders::from_reader(&mut Cursor::new(&index))?;
+    let data_file_count = headers.index.data_file_count;
+    let entry = encode_entry::<E>(path, contents)?;
+
+    let dat_path = |data_file_id: u32| {
+        let mut dat_path = base_path.as_os_str().to_owned();
+        dat_path.push(format!(".dat{}", data_file_id));
+        PathBuf::from(dat_path)
+    };
+
+    let mut data_file_id = data_file_count.saturating_sub(1);
+    let dat_size = std::fs::metadata(dat_path(data_file_id)).map_or(0, |metadata| metadata.len());
+    let offset = dat_size.next_multiple_of(ALIGNMENT as u64);
+
+    let offset = if data_file_count > 0 && offset + entry.len() as u64 <= DEFAULT_MAX_DAT_SIZE {
+        journal.back_up_append(&dat_path(data_file_id), 2 * HEADER_SIZE)?;
+        append_to_dat::<E>(&dat_path(data_file_id), offset, &entry)?
+    } else {
+        data_file_id = data_file_count;
+        if data_file_id >= MAX_DATA_FILES {
+            return Err(Error::ArchiveFull(base_path.to_string_lossy().to_string()));
+        }
+
+        journal.record_created(&dat_path(data_file_id))?;
+        let mut data_file = DataFileWriter::create(base_path, data_file_id)?;
+        let offset = data_file.append(&entry)?;
+        data_file.finish::<E>(platform, DEFAULT_MAX_DAT_SIZE)?;
+        offset
+    };
+
+    let data = SqPackIndexTableEntry::encode_data(data_file_id, offset)
+        .ok_or_else(|| Error::ArchiveFull(base_path.to_string_lossy().to_string()))?;
+    let data_file_count = data_file_count.max(data_file_id + 1);
+    let (hash1, hash2) =
+        SqPackIndexHash::from_path(path).ok_or_else(|| Error::PathNotFound(path.to_string()))?;
+
+    let mut found = false;
+    for (index_path, hash) in index_paths.iter().zip([hash1, hash2]) {
+        let mut index = std::fs::read(index_path)?;
+        let headers = SqPackIndexHeaders::from_reader(&mut Cursor::new(&index))?;
+        if !patch_index::<E>(&mut index, &headers, hash, path, data, data_file_count)? {
+            continue;
+        }
+
+        journal.back_up(index_path)?;
+        std::fs::write(index_path, index)?;
+        found = true;
+    }
+
+    if !found {
+        return Err(Error::PathNotFound(path.to_string()));
+    }
+    Ok(())
+}
+
+/// Appends an aligned entry to an existing `.datN`, updating the data size & digest in its
+/// header.
+fn append_to_dat<E: ByteOrder>(dat_path: &Path, offset: u64, entry: &[u8]) -> Result<u64, Error> {
+    let mut file = OpenOptions::new().read(true).write(true).open(dat_path)?;
+    file.set_len(offset)?;
+    file.seek(SeekFrom::Start(offset))?;
+    file.write_all(entry)?;
+
+    let mut header = vec![0; HEADER_SIZE];
+    file.seek(SeekFrom::Start(HEADER_SIZE as u64))?;
+    file.read_exact(&mut header)?;
+
+    let data_size = offset + entry.len() as u64 - FIRST_ENTRY_OFFSET;
+    E::write_u32(
+        &mut header[DATA_SIZE_OFFSET..],
+        u32::try_from(data_size).unwrap_or(u32::MAX),
+    );
+    header[DATA_DIGEST_OFFSET..DATA_DIGEST_OFFSET + 20]
+        .copy_from_slice(&data_digest(&mut file, data_size)?);
+    header.truncate(INDEX_HEADER_DIGEST_OFFSET as usize);
+
+    file.seek(SeekFrom::Start(HEADER_SIZE as u64))?;
+    file.write_all(&with_header_digest(header))?;
+    file.flush()?;
+    Ok(offset)
+}
+
+/// SHA-1 of a `.datN`'s data, which starts after its headers.
+fn data_digest(file: &mut (impl Read + Seek), data_size: u64) -> Result<[u8; 20], Error> {
+    file.seek(SeekFrom::Start(FIRST_ENTRY_OFFSET))?;
+    let mut data = file.take(data_size);
+    let mut sha1 = sha1_smol::Sha1::new();
+    let mut buf = [0; 0x10000];
+    loop {
+        match data.read(&mut buf)? {
+            0 => break,
+            count => sha1.update(&buf[..count]),
+        }
+    }
+    Ok(sha1.digest().bytes())
+}
+
+/// Points a file's entry at new data, then updates the index's digests. Returns false if the
+/// index doesn't hold the file.
+fn patch_index<E: ByteOrder>(
+    index: &mut [u8],
+    headers: &SqPackIndexHeaders,
+    hash: SqPackIndexHash,
+    path: &str,
+    data: u32,
+    data_file_count: u32,
+) -> Result<bool, Error> {
+    let (entry_size, hash_values) = match hash {
+        SqPackIndexHash::FolderFile { folder, file } => (16, vec![file, folder]),
+        SqPackIndexHash::FullPath(hash) => (8, vec![hash]),
+    };
+    let data_offset = 4 * hash_values.len();
+
+    // The segments come from the index itself, so every access is checked against its length
+    let truncated = || Error::Truncated { path: None };
+
+    let files = &headers.index.files;
+    let mut data_position = None;
+    let files_end = files.offset as usize + files.size as usize;
+    for position in (files.offset as usize..files_end).step_by(entry_size) {
+        let mut reader = index.get(position..position + entry_size).unwrap_or(&[]);
+        let values = (0..hash_values.len())
+            .map(|_| reader.read_u32::<E>())
+            .collect::<Result<Vec<_>, _>>()?;
+        if values == hash_values {
+            data_position = Some(position + data_offset);
+            break;
+        }
+    }
+    let Some(mut data_position) = data_position else {
+        return Ok(false);
+    };
+
+    // Files sharing a hash are told apart by their paths, in the synonym table
+    let entry_data = index
+        .get(data_position..data_position + 4)
+        .ok_or_else(truncated)?;
+    if E::read_u32(entry_data) & 1 != 0 {
+        let synonyms = &headers.index.synonyms;
+        let synonyms_end = synonyms.offset as usize + synonyms.size as usize;
+        let mut synonym = None;
+        for position in (synonyms.offset as usize..synonyms_end).step_by(SYNONYM_ENTRY_SIZE) {
+            let path_field = index
+                .get(position + SYNONYM_PATH_OFFSET..position + SYNONYM_ENTRY_SIZE)
+                .ok_or_else(truncated)?;
+            let length = path_field.iter().position(|&c| c == 0).unwrap_or(0);
+            if path_field[..length].eq_ignore_ascii_case(path.as_bytes()) {
+                synonym = Some(position);
+                break;
+            }
+        }
+        let Some(synonym) = synonym else {
+            return Ok(false);
+        };
+        data_position = synonym + 8;
+    }
+    let entry_data = index
+        .get_mut(data_position..data_position + 4)
+        .ok_or_else(truncated)?;
+    E::write_u32(entry_data, data);
+
+    // Every segment digest is refreshed, except for those the index leaves unset
+    let header_offset = headers.sqpack.size as usize;
+    let segments = [
+        &headers.index.files,
+        &headers.index.synonyms,
+        &headers.index.empty_blocks,
+        &headers.index.folders,
+    ];
+    for (segment, field_offset) in segments.into_iter().zip(SEGMENT_FIELD_OFFSETS) {
+        if segment.digest.iter().all(|&b| b == 0) {
+            continue;
+        }
+        let start = segment.offset as usize;
+        let segment_data = index
+            .get(start..start + segment.size as usize)
+            .ok_or_else(truncated)?;
+        let digest = sha1_smol::Sha1::from(segment_data).digest().bytes();
+        let digest_offset = header_offset + field_offset + 8;
+        index
+            .get_mut(digest_offset..digest_offset + 20)
+            .ok_or_else(truncated)?
+            .copy_from_slice(&digest);
+    }
+    let count_offset = header_offset + DATA_FILE_COUNT_OFFSET;
+    let count_field = index
+        .get_mut(count_offset..count_offset + 4)
+        .ok_or_else(truncated)?;
+    E::write_u32(count_field, data_file_count);
+
+    let header_end = header_offset + INDEX_HEADER_DIGEST_OFFSET as usize;
+    let header = index
+        .get(header_offset..header_end)
+        .ok_or_else(truncated)?
+        .to_vec();
+    index
+        .get_mut(header_offset..header_offset + HEADER_SIZE)
+        .ok_or_else(truncated)?
+        .copy_from_slice(&with_header_digest(header));
+    Ok(true)
+}
@@ -0,0 +1,802 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufWriter, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt, WriteBytesExt};
+use flate2::{write::DeflateEncoder, Compression};
+
+use crate::{
+    error::Error,
+    file_key::FileKey,
+    sqpack::{PlatformId, SqPackIndexHash, SqPackIndexTableEntry, SQPACK_HEADER_DIGEST_OFFSET},
+};
+
+//////////////////////////////////////////
+
+/// The game never lets a `.datN` file grow past this size.
+pub const DEFAULT_MAX_DAT_SIZE: u64 = 2_000_000_000;
+
+/// Entries are referenced by a 3-bit data file id, so there can be at most 8 `.datN` files.
+pub(crate) const MAX_DATA_FILES: u32 = 8;
+
+/// Size of the SqPack header, the index/data header, and so the offset of the first entry.
+pub(crate) const HEADER_SIZE: usize = 0x400;
+pub(crate) const FIRST_ENTRY_OFFSET: u64 = 2 * HEADER_SIZE as u64;
+
+/// Entries, and the blocks within them, are aligned to 128 bytes.
+pub(crate) const ALIGNMENT: usize = 0x80;
+
+/// Maximum uncompressed size of a single block.
+const MAX_BLOCK_SIZE: usize = 16000;
+
+/// Blocks stored without compression carry this value in place of their compressed size.
+const UNCOMPRESSED_BLOCK_SIZE: u32 = 32000;
+
+const BLOCK_HEADER_SIZE: u32 = 16;
+const COMMON_HEADER_SIZE: usize = 24;
+const MODEL_HEADER_SIZE: usize = 0x44;
+const TEX_HEADER_SIZE: usize = 0x50;
+const SYNONYM_PATH_SIZE: usize = 0xF0;
+
+//////////////////////////////////////////
+
+/// Builds `.index`, `.index2` & `.datN` files from a set of files.
+///
+/// `.tex` & `.mdl` files are stored with their texture & model layouts, falling back to the
+/// standard layout if they can't be parsed. Everything else uses the standard layout.
+pub struct SqPackWriter {
+    platform: PlatformId,
+    max_dat_size: u64,
+    files: BTreeMap<String, Box<[u8]>>,
+}
+
+impl SqPackWriter {
+    pub fn new(platform: PlatformId) -> Self {
+        Self {
+            platform,
+            max_dat_size: DEFAULT_MAX_DAT_SIZE,
+            files: BTreeMap::new(),
+        }
+    }
+
+    /// Starts a new `.datN` file whenever the current one would grow past `max_dat_size`.
+    /// Offsets are read back as 32 bits, so larger sizes are capped at `u32::MAX`.
+    pub fn with_max_dat_size(mut self, max_dat_size: u64) -> Self {
+        self.max_dat_size = max_dat_size.min(u32::MAX as u64);
+        self
+    }
+
+    /// Adds a file, replacing any previous file with the same path.
+    pub fn add_file(
+        &mut self,
+        path: impl AsRef<str>,
+        contents: impl Into<Box<[u8]>>,
+    ) -> Result<(), Error> {
+        let path = path.as_ref().to_lowercase();
+        FileKey::new(&path)?;
+        self.files.insert(path, contents.into());
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Writes every file under the `sqpack` directory at `game_path`, e.g. `exd/root.exl` to
+    /// `ffxiv/0a0000.win32.*`. Returns the keys of the index files written.
+    pub fn write(&self, game_path: impl AsRef<Path>) -> Result<Vec<FileKey>, Error> {
+        let game_path = game_path.as_ref();
+
+        let mut file_keys = BTreeMap::<FileKey, Vec<(&str, &[u8])>>::new();
+        for (path, contents) in &self.files {
+            file_keys
+                .entry(FileKey::new(path)?)
+                .or_default()
+                .push((path, contents));
+        }
+
+        for (file_key, files) in &file_keys {
+            let repository_path = game_path.join(file_key.repository.to_string());
+            std::fs::create_dir_all(&repository_path)?;
+            let base_path =
+                repository_path.join(format!("{}.{}", file_key, self.platform.file_extension()));
+
+            if self.platform.is_big_endian() {
+                self.write_file_key::<BigEndian>(*file_key, &base_path, files)?;
+            } else {
+                self.write_file_key::<LittleEndian>(*file_key, &base_path, files)?;
+            }
+        }
+
+        Ok(file_keys.into_keys().collect())
+    }
+
+    fn write_file_key<E: ByteOrder>(
+        &self,
+        file_key: FileKey,
+        base_path: &Path,
+        files: &[(&str, &[u8])],
+    ) -> Result<(), Error> {
+        let mut locations = Vec::with_capacity(files.len());
+        let mut data_file = DataFileWriter::create(base_path, 0)?;
+
+        for (path, contents) in files {
+            let entry = encode_entry::<E>(path, contents)?;
+
+            let full = data_file.size + entry.len() as u64 > self.max_dat_size;
+            if full && data_file.size > FIRST_ENTRY_OFFSET {
+                let data_file_id = data_file.data_file_id + 1;
+                if data_file_id >= MAX_DATA_FILES {
+                    return Err(Error::ArchiveFull(file_key.to_string()));
+                }
+                data_file.finish::<E>(self.platform, self.max_dat_size)?;
+                data_file = DataFileWriter::create(base_path, data_file_id)?;
+            }
+
+            let offset = data_file.append(&entry)?;
+            locations.push(FileLocation {
+                path,
+                data_file_id: data_file.data_file_id,
+                offset,
+            });
+        }
+        let data_file_count = data_file.data_file_id + 1;
+        data_file.finish::<E>(self.platform, self.max_dat_size)?;
+
+        let mut index_path = base_path.as_os_str().to_owned();
+        index_path.push(".index");
+        IndexTables::build1::<E>(&locations)?.write::<E>(
+            &index_path,
+            self.platform,
+            data_file_count,
+        )?;
+
+        index_path.push("2");
+        IndexTables::build2::<E>(&locations)?.write::<E>(
+            &index_path,
+            self.platform,
+            data_file_count,
+        )
+    }
+}
+
+//////////////////////////////////////////
+
+struct FileLocation<'a> {
+    pub path: &'a str,
+    pub data_file_id: u32,
+    pub offset: u64,
+}
+
+impl FileLocation<'_> {
+    fn data(&self) -> Result<u32, Error> {
+        SqPackIndexTableEntry::encode_data(self.data_file_id, self.offset)
+            .ok_or_else(|| Error::ArchiveFull(self.path.to_string()))
+    }
+}
+
+//////////////////////////////////////////
+
+pub(crate) struct DataFileWriter {
+    writer: BufWriter<File>,
+    data_file_id: u32,
+    size: u64,
+    sha1: sha1_smol::Sha1,
+}
+
+impl DataFileWriter {
+    pub(crate) fn create(base_path: &Path, data_file_id: u32) -> Result<Self, Error> {
+        let mut file_path = base_path.as_os_str().to_owned();
+        file_path.push(format!(".dat{}", data_file_id));
+
+        let mut writer = BufWriter::new(File::create(file_path)?);
+        // The headers are written last, once the data's digest is known
+        writer.write_all(&[0; 2 * HEADER_SIZE])?;
+
+        Ok(Self {
+            writer,
+            data_file_id,
+            size: FIRST_ENTRY_OFFSET,
+            sha1: sha1_smol::Sha1::new(),
+        })
+    }
+
+    /// Appends an already aligned entry, returning its offset.
+    pub(crate) fn append(&mut self, entry: &[u8]) -> Result<u64, Error> {
+        let offset = self.size;
+        self.writer.write_all(entry)?;
+        self.sha1.update(entry);
+        self.size += entry.len() as u64;
+        Ok(offset)
+    }
+
+    pub(crate) fn finish<E: ByteOrder>(
+        mut self,
+        platform: PlatformId,
+        max_dat_size: u64,
+    ) -> Result<(), Error> {
+        let mut header = Vec::with_capacity(HEADER_SIZE);
+        header.write_u32::<E>(HEADER_SIZE as u32)?;
+        header.write_u32::<E>(0)?;
+        header.write_u32::<E>(0x10)?;
+        header.write_u32::<E>((self.size - FIRST_ENTRY_OFFSET) as u32)?;
+        header.write_u32::<E>(self.data_file_id + 1)?;
+        header.write_u32::<E>(0)?;
+        header.write_u64::<E>(max_dat_size)?;
+        header.write_u64::<E>(0)?;
+        write_digest(&mut header, self.sha1.digest().bytes());
+
+        self.writer.seek(SeekFrom::Start(0))?;
+        self.writer.write_all(&sqpack_header::<E>(platform, 1)?)?;
+        self.writer.write_all(&with_header_digest(header))?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+//////////////////////////////////////////
+
+/// The tables of a single `.index` or `.index2` file.
+struct IndexTables {
+    files: Vec<u8>,
+    synonyms: Vec<u8>,
+    folders: Vec<u8>,
+}
+
+impl IndexTables {
+    fn build1<E: ByteOrder>(locations: &[FileLocation]) -> Result<Self, Error> {
+        let mut hashes = BTreeMap::<(u32, u32), Vec<&FileLocation>>::new();
+        for location in locations {
+            if let Some((SqPackIndexHash::FolderFile { folder, file }, _)) =
+                SqPackIndexHash::from_path(location.path)
+            {
+                hashes.entry((folder, file)).or_default().push(location);
+            }
+        }
+
+        let mut tables = Self::empty();
+        let mut folders = BTreeMap::<u32, (u32, u32)>::new();
+        for ((folder, file), locations) in hashes {
+            let offset = FIRST_ENTRY_OFFSET as u32 + tables.files.len() as u32;
+            let (_, size) = folders.entry(folder).or_insert((offset, 0));
+            *size += 16;
+
+            tables.files.write_u32::<E>(file)?;
+            tables.files.write_u32::<E>(folder)?;
+            tables.write_data::<E>(&locations, |synonyms, location, index| {
+                synonyms.write_u32::<E>(file)?;
+                synonyms.write_u32::<E>(folder)?;
+                synonyms.write_u32::<E>(location.data()?)?;
+                synonyms.write_u32::<E>(index)?;
+                write_synonym_path(synonyms, location.path)
+            })?;
+            tables.files.write_u32::<E>(0)?;
+        }
+        tables.finish_synonyms();
+
+        // The folder table follows the file & synonym tables, pointing into the file table
+        for (folder, (offset, size)) in folders {
+            tables.folders.write_u32::<E>(folder)?;
+            tables.folders.write_u32::<E>(offset)?;
+            tables.folders.write_u32::<E>(size)?;
+            tables.folders.write_u32::<E>(0)?;
+        }
+        Ok(tables)
+    }
+
+    fn build2<E: ByteOrder>(locations: &[FileLocation]) -> Result<Self, Error> {
+        let mut hashes = BTreeMap::<u32, Vec<&FileLocation>>::new();
+        for location in locations {
+            if let Some((_, SqPackIndexHash::FullPath(hash))) =
+                SqPackIndexHash::from_path(location.path)
+            {
+                hashes.entry(hash).or_default().push(location);
+            }
+        }
+
+        let mut tables = Self::empty();
+        for (hash, locations) in hashes {
+            tables.files.write_u32::<E>(hash)?;
+            tables.write_data::<E>(&locations, |synonyms, location, index| {
+                synonyms.write_u32::<E>(hash)?;
+                synonyms.write_u32::<E>(0)?;
+                synonyms.write_u32::<E>(location.data()?)?;
+                synonyms.write_u32::<E>(index)?;
+                write_synonym_path(synonyms, location.path)
+            })?;
+        }
+        tables.finish_synonyms();
+        Ok(tables)
+    }
+
+    fn empty() -> Self {
+        Self {
+            files: Vec::new(),
+            synonyms: Vec::new(),
+            folders: Vec::new(),
+        }
+    }
+
+    /// Writes the data field of a file table entry. Files sharing a hash are flagged as
+    /// synonyms, and written to the synonym table along with their paths instead.
+    fn write_data<E: ByteOrder>(
+        &mut self,
+        locations: &[&FileLocation],
+        mut write_synonym: impl FnMut(&mut Vec<u8>, &FileLocation, u32) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        match locations {
+            [location] => self.files.write_u32::<E>(location.data()?)?,
+            _ => {
+                self.files.write_u32::<E>(1)?;
+                for (index, location) in locations.iter().enumerate() {
+                    write_synonym(&mut self.synonyms, location, index as u32)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The synonym table ends with a blank entry.
+    fn finish_synonyms(&mut self) {
+        self.synonyms.extend_from_slice(&[0xFF; 16]);
+        self.synonyms.extend_from_slice(&[0; SYNONYM_PATH_SIZE]);
+    }
+
+    fn write<E: ByteOrder>(
+        &self,
+        file_path: impl AsRef<Path>,
+        platform: PlatformId,
+        data_file_count: u32,
+    ) -> Result<(), Error> {
+        let segments: [&[u8]; 4] = [&self.files, &self.synonyms, &[], &self.folders];
+
+        let mut header = Vec::with_capacity(HEADER_SIZE);
+        header.write_u32::<E>(HEADER_SIZE as u32)?;
+        header.write_u32::<E>(1)?;
+        let mut offset = FIRST_ENTRY_OFFSET as u32;
+        for (i, segment) in segments.iter().enumerate() {
+            header.write_u32::<E>(offset)?;
+            header.write_u32::<E>(segment.len() as u32)?;
+            write_digest(&mut header, sha1_smol::Sha1::from(segment).digest().bytes());
+            if i == 0 {
+                header.write_u32::<E>(data_file_count)?;
+            }
+            offset += segment.len() as u32;
+        }
+        header.write_u32::<E>(0)?;
+
+        let mut writer = BufWriter::new(File::create(file_path)?);
+        writer.write_all(&sqpack_header::<E>(platform, 2)?)?;
+        writer.write_all(&with_header_digest(header))?;
+        for segment in segments {
+            writer.write_all(segment)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+fn write_synonym_path(synonyms: &mut Vec<u8>, path: &str) -> Result<(), Error> {
+    // The path must be NUL-terminated within its field
+    if path.len() >= SYNONYM_PATH_SIZE {
+        return Err(Error::unknown_value("synonym path", path));
+    }
+    let mut field = [0; SYNONYM_PATH_SIZE];
+    field[..path.len()].copy_from_slice(path.as_bytes());
+    synonyms.extend_from_slice(&field);
+    Ok(())
+}
+
+//////////////////////////////////////////
+
+fn sqpack_header<E: ByteOrder>(platform: PlatformId, file_type: u32) -> Result<Vec<u8>, Error> {
+    let mut header = Vec::with_capacity(HEADER_SIZE);
+    header.extend_from_slice(b"SqPack\0\0");
+    header.write_u8(platform as u8)?;
+    header.extend_from_slice(&[0; 3]);
+    header.write_u32::<E>(HEADER_SIZE as u32)?;
+    header.write_u32::<E>(1)?;
+    header.write_u32::<E>(file_type)?;
+    Ok(with_header_digest(header))
+}
+
+/// Pads a SqPack, index or data header, filling in the SHA-1 of everything before its digest.
+pub(crate) fn with_header_digest(mut header: Vec<u8>) -> Vec<u8> {
+    // Every header keeps its digest at the same offset
+    header.resize(SQPACK_HEADER_DIGEST_OFFSET as usize, 0);
+    let digest = sha1_smol::Sha1::from(&header).digest().bytes();
+    write_digest(&mut header, digest);
+    header.resize(HEADER_SIZE, 0);
+    header
+}
+
+/// Digests are stored in 64-byte fields, of which SHA-1 only uses the first 20.
+fn write_digest(out: &mut Vec<u8>, digest: [u8; 20]) {
+    out.extend_from_slice(&digest);
+    out.extend_from_slice(&[0; 64 - 20]);
+}
+
+//////////////////////////////////////////
+
+/// Encodes a file as a complete, aligned `.dat` entry.
+pub(crate) fn encode_entry<E: ByteOrder>(path: &str, contents: &[u8]) -> Result<Vec<u8>, Error> {
+    let layout = match path.rsplit_once('.').map(|(_, extension)| extension) {
+        Some("tex") => TextureLayout::from_contents::<E>(contents).map(EntryLayout::Texture),
+        Some("mdl") => ModelLayout::from_contents::<E>(contents).map(EntryLayout::Model),
+        _ => None,
+    };
+
+    match layout {
+        Some(EntryLayout::Texture(layout)) => encode_texture::<E>(contents, &layout),
+        Some(EntryLayout::Model(layout)) => encode_model::<E>(contents, &layout),
+        None => encode_standard::<E>(contents),
+    }
+}
+
+enum EntryLayout {
+    Texture(TextureLayout),
+    Model(ModelLayout),
+}
+
+/// Compresses data into blocks of at most `MAX_BLOCK_SIZE` bytes, returning each block's size.
+fn encode_blocks<E: ByteOrder>(data: &[u8], out: &mut Vec<u8>) -> Result<Vec<u16>, Error> {
+    data.chunks(MAX_BLOCK_SIZE)
+        .map(|chunk| {
+            let start = out.len();
+
+            let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(chunk)?;
+            let compressed = encoder.finish()?;
+
+            out.write_u32::<E>(BLOCK_HEADER_SIZE)?;
+            out.write_u32::<E>(0)?;
+            if compressed.len() < chunk.len() {
+                out.write_u32::<E>(compressed.len() as u32)?;
+                out.write_u32::<E>(chunk.len() as u32)?;
+                out.extend_from_slice(&compressed);
+            } else {
+                out.write_u32::<E>(UNCOMPRESSED_BLOCK_SIZE)?;
+                out.write_u32::<E>(chunk.len() as u32)?;
+                out.extend_from_slice(chunk);
+            }
+
+            pad(out, start);
+            Ok((out.len() - start) as u16)
+        })
+        .collect()
+}
+
+/// Pads `out` so that the data written since `start` is a multiple of `ALIGNMENT`.
+fn pad(out: &mut Vec<u8>, start: usize) {
+    let size = out.len() - start;
+    out.resize(start + size.next_multiple_of(ALIGNMENT), 0);
+}
+
+/// The common header at the start of every entry. For models, `block_count` is the version.
+fn common_header<E: ByteOrder>(
+    out: &mut Vec<u8>,
+    header_size: usize,
+    file_type: u32,
+    file_size: usize,
+    block_count: u32,
+    data_size: usize,
+) -> Result<(), Error> {
+    let data_size = (data_size / ALIGNMENT) as u32;
+    out.write_u32::<E>(header_size as u32)?;
+    out.write_u32::<E>(file_type)?;
+    out.write_u32::<E>(file_size as u32)?;
+    out.write_u32::<E>(data_size)?;
+    out.write_u32::<E>(data_size)?;
+    out.write_u32::<E>(block_count)?;
+    Ok(())
+}
+
+fn encode_standard<E: ByteOrder>(contents: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut blocks = Vec::new();
+    let block_sizes = encode_blocks::<E>(contents, &mut blocks)?;
+
+    let header_size = (COMMON_HEADER_SIZE + 8 * block_sizes.len()).next_multiple_of(ALIGNMENT);
+    let mut entry = Vec::with_capacity(header_size + blocks.len());
+    common_header::<E>(
+        &mut entry,
+        header_size,
+        2,
+        contents.len(),
+        block_sizes.len() as u32,
+        blocks.len(),
+    )?;
+
+    let mut offset = 0;
+    for (block_size, chunk) in block_sizes.iter().zip(contents.chunks(MAX_BLOCK_SIZE)) {
+        entry.write_u32::<E>(offset)?;
+        entry.write_u16::<E>(*block_size)?;
+        entry.write_u16::<E>(chunk.len() as u16)?;
+        offset += *block_size as u32;
+    }
+
+    entry.resize(header_size, 0);
+    entry.append(&mut blocks);
+    Ok(entry)
+}
+
+//////////////////////////////////////////
+
+/// Where the header & each mip level of a `.tex` file are.
+struct TextureLayout {
+    header_size: usize,
+    lods: Vec<(usize, usize)>,
+}
+
+impl TextureLayout {
+    /// Reads the surface offsets of a `.tex` header, which must be contiguous for the file to
+    /// be rebuilt exactly when read back.
+    fn from_contents<E: ByteOrder>(contents: &[u8]) -> Option<Self> {
+        let mut reader = contents.get(..TEX_HEADER_SIZE)?;
+        let _attributes = reader.read_u32::<E>().ok()?;
+        let _format = reader.read_u32::<E>().ok()?;
+        let _width = reader.read_u16::<E>().ok()?;
+        let _height = reader.read_u16::<E>().ok()?;
+        let _depth = reader.read_u16::<E>().ok()?;
+        let mip_count = (reader.read_u16::<E>().ok()? & 0xFF) as usize;
+        let _lod_offsets = (0..3)
+            .map(|_| reader.read_u32::<E>())
+            .collect::<Result<Vec<_>, _>>()
+            .ok()?;
+        let surface_offsets = (0..13)
+            .map(|_| reader.read_u32::<E>().map(|offset| offset as usize))
+            .collect::<Result<Vec<_>, _>>()
+            .ok()?;
+
+        let header_size = surface_offsets[0];
+        if mip_count == 0 || mip_count > surface_offsets.len() || header_size < TEX_HEADER_SIZE {
+            return None;
+        }
+
+        let mut lods = Vec::<(usize, usize)>::with_capacity(mip_count);
+        for mip in 0..mip_count {
+            let start = surface_offsets[mip];
+            let end = if mip + 1 < mip_count {
+                surface_offsets[mip + 1]
+            } else {
+                contents.len()
+            };
+            if start != lods.last().map_or(header_size, |&(_, end)| end) || start > end {
+                return None;
+            }
+            lods.push((start, end));
+        }
+
+        Some(Self { header_size, lods })
+    }
+}
+
+fn encode_texture<E: ByteOrder>(contents: &[u8], layout: &TextureLayout) -> Result<Vec<u8>, Error> {
+    // The .tex header is stored uncompressed, directly followed by each LOD's blocks
+    let mut blocks = contents[..layout.header_size].to_vec();
+    let mut lod_infos = Vec::with_capacity(layout.lods.len());
+    let mut block_sizes = Vec::new();
+    for &(start, end) in &layout.lods {
+        let offset = blocks.len();
+        let sizes = encode_blocks::<E>(&contents[start..end], &mut blocks)?;
+        lod_infos.push((
+            offset,
+            blocks.len() - offset,
+            end - start,
+            block_sizes.len(),
+            sizes.len(),
+        ));
+        block_sizes.extend(sizes);
+    }
+
+    let header_size = (COMMON_HEADER_SIZE + 20 * lod_infos.len() + 2 * block_sizes.len())
+        .next_multiple_of(ALIGNMENT);
+    let mut entry = Vec::with_capacity(header_size + blocks.len());
+    common_header::<E>(
+        &mut entry,
+        header_size,
+        4,
+        contents.len(),
+        lod_infos.len() as u32,
+        blocks.len(),
+    )?;
+
+    for (offset, compressed_size, decompressed_size, block_offset, block_count) in lod_infos {
+        entry.write_u32::<E>(offset as u32)?;
+        entry.write_u32::<E>(compressed_size as u32)?;
+        entry.write_u32::<E>(decompressed_size as u32)?;
+        entry.write_u32::<E>(block_offset as u32)?;
+        entry.write_u32::<E>(block_count as u32)?;
+    }
+    for block_size in block_sizes {
+        entry.write_u16::<E>(block_size)?;
+    }
+
+    entry.resize(header_size, 0);
+    entry.append(&mut blocks);
+    pad(&mut entry, 0);
+    Ok(entry)
+}
+
+//////////////////////////////////////////
+
+/// The `.mdl` header fields which aren't rebuilt from the section sizes.
+struct ModelLayout {
+    version: u32,
+    vertex_declaration_count: u16,
+    material_count: u16,
+    lod_count: u8,
+    index_buffer_streaming_enabled: u8,
+    edge_geometry_enabled: u8,
+    /// Start & end of the stack, runtime, vertex buffer, edge geometry vertex buffer & index
+    /// buffer sections, in the order of the entry's tables.
+    sections: [(usize, usize); 11],
+}
+
+impl ModelLayout {
+    /// Splits a `.mdl` file into its sections, which must be laid out the way the reader
+    /// rebuilds them for the file to come back byte for byte.
+    fn from_contents<E: ByteOrder>(contents: &[u8]) -> Option<Self> {
+        let mut reader = contents.get(..MODEL_HEADER_SIZE)?;
+        let version = reader.read_u32::<E>().ok()?;
+        let stack_size = reader.read_u32::<E>().ok()? as usize;
+        let runtime_size = reader.read_u32::<E>().ok()? as usize;
+        let vertex_declaration_count = reader.read_u16::<E>().ok()?;
+        let material_count = reader.read_u16::<E>().ok()?;
+        let mut read_u32s = || {
+            let mut values = [0; 3];
+            for value in &mut values {
+                *value = reader.read_u32::<E>().ok()? as usize;
+            }
+            Some(values)
+        };
+        let vertex_offsets = read_u32s()?;
+        let index_offsets = read_u32s()?;
+        let vertex_sizes = read_u32s()?;
+        let index_sizes = read_u32s()?;
+        let lod_count = reader.read_u8().ok()?;
+        let index_buffer_streaming_enabled = reader.read_u8().ok()?;
+        let edge_geometry_enabled = reader.read_u8().ok()?;
+
+        let mut end = MODEL_HEADER_SIZE;
+        let mut next_section = |offset: usize, size: usize| {
+            if size == 0 {
+                return Some((end, end));
+            }
+            if offset != end {
+                return None;
+            }
+            end += size;
+            Some((offset, end))
+        };
+
+        let mut sections = [(0, 0); 11];
+        sections[0] = next_section(MODEL_HEADER_SIZE, stack_size)?;
+        sections[1] = next_section(sections[0].1, runtime_size)?;
+        for lod in 0..3 {
+            sections[2 + lod] = next_section(vertex_offsets[lod], vertex_sizes[lod])?;
+            sections[5 + lod] = next_section(0, 0)?;
+            sections[8 + lod] = next_section(index_offsets[lod], index_sizes[lod])?;
+        }
+
+        let layout = Self {
+            version,
+            vertex_declaration_count,
+            material_count,
+            lod_count,
+            index_buffer_streaming_enabled,
+            edge_geometry_enabled,
+            sections,
+        };
+        (end == contents.len() && layout.header::<E>().ok()? == contents[..MODEL_HEADER_SIZE])
+            .then_some(layout)
+    }
+
+    /// The `.mdl` header as the reader rebuilds it from the section sizes.
+    fn header<E: ByteOrder>(&self) -> Result<Vec<u8>, Error> {
+        let size = |(start, end): (usize, usize)| (end - start) as u32;
+        let mut vertex_offsets = [0; 3];
+        let mut index_offsets = [0; 3];
+        for lod in 0..3 {
+            for (offsets, section) in [
+                (&mut vertex_offsets, 2 + lod),
+                (&mut index_offsets, 8 + lod),
+            ] {
+                let (start, end) = self.sections[section];
+                if start != end && (lod == 0 || start as u32 != offsets[lod - 1]) {
+                    offsets[lod] = start as u32;
+                }
+            }
+        }
+
+        let mut header = Vec::with_capacity(MODEL_HEADER_SIZE);
+        header.write_u32::<E>(self.version)?;
+        header.write_u32::<E>(size(self.sections[0]))?;
+        header.write_u32::<E>(size(self.sections[1]))?;
+        header.write_u16::<E>(self.vertex_declaration_count)?;
+        header.write_u16::<E>(self.material_count)?;
+        for value in vertex_offsets
+            .into_iter()
+            .chain(index_offsets)
+            .chain((2..5).map(|section| size(self.sections[section])))
+            .chain((8..11).map(|section| size(self.sections[section])))
+        {
+            header.write_u32::<E>(value)?;
+        }
+        header.write_u8(self.lod_count)?;
+        header.write_u8(self.index_buffer_streaming_enabled)?;
+        header.write_u8(self.edge_geometry_enabled)?;
+        header.write_u8(0)?;
+        Ok(header)
+    }
+}
+
+/// The order sections are read in: stack, runtime, then each LOD's vertex, edge geometry
+/// vertex & index buffers. The block size table follows this order.
+const MODEL_SECTION_ORDER: [usize; 11] = [0, 1, 2, 5, 8, 3, 6, 9, 4, 7, 10];
+
+fn encode_model<E: ByteOrder>(contents: &[u8], layout: &ModelLayout) -> Result<Vec<u8>, Error> {
+    let mut blocks = Vec::new();
+    let mut block_sizes = Vec::new();
+    let mut sections = [(0, 0, 0, 0, 0); 11];
+    for section in MODEL_SECTION_ORDER {
+        let (start, end) = layout.sections[section];
+        let offset = blocks.len();
+        let sizes = encode_blocks::<E>(&contents[start..end], &mut blocks)?;
+        sections[section] = (
+            offset,
+            blocks.len() - offset,
+            end - start,
+            block_sizes.len(),
+            sizes.len(),
+        );
+        block_sizes.extend(sizes);
+    }
+
+    let header_size = (COMMON_HEADER_SIZE + 11 * (3 * 4 + 2 * 2) + 8 + 2 * block_sizes.len())
+        .next_multiple_of(ALIGNMENT);
+    let mut entry = Vec::with_capacity(header_size + blocks.len());
+    common_header::<E>(
+        &mut entry,
+        header_size,
+        3,
+        contents.len(),
+        layout.version,
+        blocks.len(),
+    )?;
+
+    for &(_, _, decompressed_size, _, _) in &sections {
+        entry.write_u32::<E>(decompressed_size as u32)?;
+    }
+    for &(_, compressed_size, _, _, _) in &sections {
+        entry.write_u32::<E>(compressed_size as u32)?;
+    }
+    for &(offset, _, _, _, _) in &sections {
+        entry.write_u32::<E>(offset as u32)?;
+    }
+    for &(_, _, _, block_index, _) in &sections {
+        entry.write_u16::<E>(block_index as u16)?;
+    }
+    for &(_, _, _, _, block_count) in &sections {
+        entry.write_u16::<E>(block_count as u16)?;
+    }
+    entry.write_u16::<E>(layout.vertex_declaration_count)?;
+    entry.write_u16::<E>(layout.material_count)?;
+    entry.write_u8(layout.lod_count)?;
+    entry.write_u8(layout.index_buffer_streaming_enabled)?;
+    entry.write_u8(layout.edge_geometry_enabled)?;
+    entry.write_u8(0)?;
+    for block_size in block_sizes {
+        entry.write_u16::<E>(block_size)?;
+    }
+
+    entry.resize(header_size, 0);
+    entry.append(&mut blocks);
+    Ok(entry)
+}
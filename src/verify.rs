@@ -0,0 +1,242 @@
+use std::{
+    fmt::Display,
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use byteorder::{BigEndian, LittleEndian};
+
+use crate::{
+    error::Error,
+    file_key::FileKey,
+    sqpack::{
+        SqPackDataHeader, SqPackFileType, SqPackHeader, SqPackIndexHeaders, SqPackIndexSegment,
+        SqPackIndexTableEntry, DATA_HEADER_DIGEST_OFFSET, INDEX_HEADER_DIGEST_OFFSET,
+        SQPACK_HEADER_DIGEST_OFFSET,
+    },
+};
+
+//////////////////////////////////////////
+
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    /// Number of SqPack files (.index, .index2, .datN) whose digests were checked.
+    pub files_checked: usize,
+    /// Number of index entries whose data was checked.
+    pub entries_checked: usize,
+    pub problems: Vec<VerifyProblem>,
+}
+
+impl VerifyReport {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    pub(crate) fn append(&mut self, mut other: VerifyReport) {
+        self.files_checked += other.files_checked;
+        self.entries_checked += other.entries_checked;
+        self.problems.append(&mut other.problems);
+    }
+}
+
+#[derive(Debug)]
+pub enum VerifyProblem {
+    DigestMismatch {
+        file_path: PathBuf,
+        section: &'static str,
+    },
+    /// The section holds data, but its digest was left zeroed.
+    MissingDigest {
+        file_path: PathBuf,
+        section: &'static str,
+    },
+    MissingDataFile {
+        file_key: FileKey,
+        data_file_id: u32,
+    },
+    EntryOutOfBounds {
+        file_key: FileKey,
+        entry: SqPackIndexTableEntry,
+    },
+    InvalidEntry {
+        file_key: FileKey,
+        entry: SqPackIndexTableEntry,
+        error: Error,
+    },
+    SizeMismatch {
+        file_key: FileKey,
+        entry: SqPackIndexTableEntry,
+        expected: u64,
+        actual: u64,
+    },
+}
+
+impl Display for VerifyProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VerifyProblem::DigestMismatch { file_path, section } => {
+                write!(f, "{}: SHA-1 mismatch for {}", file_path.display(), section)
+            }
+            VerifyProblem::MissingDigest { file_path, section } => {
+                write!(f, "{}: no SHA-1 for {}", file_path.display(), section)
+            }
+            VerifyProblem::MissingDataFile {
+                file_key,
+                data_file_id,
+            } => write!(f, "{}: missing dat{}", file_key, data_file_id),
+            VerifyProblem::EntryOutOfBounds { file_key, entry } => write!(
+                f,
+                "{}: {:?} points outside of dat{}",
+                file_key, entry.hash, entry.data_file_id
+            ),
+            VerifyProblem::InvalidEntry {
+                file_key,
+                entry,
+                error,
+            } => write!(f, "{}: {:?} is invalid: {}", file_key, entry.hash, error),
+            VerifyProblem::SizeMismatch {
+                file_key,
+                entry,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{}: {:?} decompressed to {} bytes, expected {}",
+                file_key, entry.hash, actual, expected
+            ),
+        }
+    }
+}
+
+//////////////////////////////////////////
+
+/// Checks the header digests of a single SqPack file, along with the segment digests of index
+/// files & the data digest of `.datN` files.
+pub fn verify_sqpack_file(file_path: impl AsRef<Path>) -> Result<VerifyReport, Error> {
+    let file_path = file_path.as_ref();
+    verify_sqpack_reader(&mut BufReader::new(File::open(file_path)?), file_path)
+}
+
+/// Like `verify_sqpack_file`, for a file which is already open. `file_path` is only used to
+/// name it in the report.
+pub(crate) fn verify_sqpack_reader<R: Read + Seek>(
+    reader: &mut R,
+    file_path: &Path,
+) -> Result<VerifyReport, Error> {
+    let mut report = VerifyReport {
+        files_checked: 1,
+        ..Default::default()
+    };
+
+    let mut check =
+        |reader: &mut R, section: &'static str, start: u32, size: u32, digest: &[u8; 20]| {
+            // Unused sections are left zeroed, rather than holding the digest of nothing
+            if digest.iter().all(|&b| b == 0) {
+                if size > 0 {
+                    report.problems.push(VerifyProblem::MissingDigest {
+                        file_path: file_path.to_path_buf(),
+                        section,
+                    });
+                }
+                return Ok::<_, Error>(());
+            }
+
+            if sha1_of(reader, start, size)?.as_ref() != Some(digest) {
+                report.problems.push(VerifyProblem::DigestMismatch {
+                    file_path: file_path.to_path_buf(),
+                    section,
+                });
+            }
+            Ok(())
+        };
+
+    let sqpack_header =
+        SqPackHeader::from_reader(reader).map_err(|e| e.with_path(file_path.to_string_lossy()))?;
+    check(
+        reader,
+        "SqPack header",
+        0,
+        SQPACK_HEADER_DIGEST_OFFSET,
+        &sqpack_header.digest,
+    )?;
+
+    if sqpack_header.file_type == SqPackFileType::Data {
+        reader.seek(SeekFrom::Start(sqpack_header.size.into()))?;
+        let data_header = if sqpack_header.platform_id.is_big_endian() {
+            SqPackDataHeader::from_reader::<BigEndian, R>(reader)
+        } else {
+            SqPackDataHeader::from_reader::<LittleEndian, R>(reader)
+        }
+        .map_err(|e| e.with_path(file_path.to_string_lossy()))?;
+        check(
+            reader,
+            "data header",
+            sqpack_header.size,
+            DATA_HEADER_DIGEST_OFFSET,
+            &data_header.digest,
+        )?;
+        check(
+            reader,
+            "data",
+            sqpack_header.size.saturating_add(data_header.size),
+            data_header.data_size,
+            &data_header.data_digest,
+        )?;
+    }
+
+    if sqpack_header.file_type == SqPackFileType::Index {
+        let index_header = SqPackIndexHeaders::from_reader(reader)
+            .map_err(|e| e.with_path(file_path.to_string_lossy()))?
+            .index;
+        check(
+            reader,
+            "index header",
+            sqpack_header.size,
+            INDEX_HEADER_DIGEST_OFFSET,
+            &index_header.digest,
+        )?;
+
+        let segments: [(&'static str, &SqPackIndexSegment); 4] = [
+            ("file segment", &index_header.files),
+            ("synonym segment", &index_header.synonyms),
+            ("empty block segment", &index_header.empty_blocks),
+            ("folder segment", &index_header.folders),
+        ];
+        for (section, segment) in segments {
+            check(
+                reader,
+                section,
+                segment.offset,
+                segment.size,
+                &segment.digest,
+            )?;
+        }
+    }
+
+    Ok(report)
+}
+
+/// SHA-1 of the given range, or `None` if the file ends before the range does.
+fn sha1_of(
+    reader: &mut (impl Read + Seek),
+    start: u32,
+    size: u32,
+) -> Result<Option<[u8; 20]>, Error> {
+    reader.seek(SeekFrom::Start(start as u64))?;
+
+    let mut sha1 = sha1_smol::Sha1::new();
+    let mut remaining = size as usize;
+    let mut buf = [0; 0x1000];
+    while remaining > 0 {
+        let chunk_size = remaining.min(buf.len());
+        let count = reader.read(&mut buf[..chunk_size])?;
+        if count == 0 {
+            return Ok(None);
+        }
+        sha1.update(&buf[..count]);
+        remaining -= count;
+    }
+
+    Ok(Some(sha1.digest().bytes()))
+}
@@ -0,0 +1,115 @@
+use std::{fmt::Display, path::Path, str::FromStr};
+
+use crate::error::Error;
+
+//////////////////////////////////////////
+
+/// A game version, as stored in `ffxivgame.ver` & `sqpack/exN/exN.ver`, e.g. `2024.07.23.0000.0000`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GameVersion {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub build: u16,
+    pub revision: u16,
+}
+
+impl GameVersion {
+    pub fn from_file(file_path: impl AsRef<Path>) -> Result<Self, Error> {
+        std::fs::read_to_string(file_path)?.parse()
+    }
+}
+
+impl FromStr for GameVersion {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let version = s.trim();
+        let invalid = || Error::InvalidVersion(version.to_string());
+
+        let parts = version.split('.').collect::<Vec<_>>();
+        let [year, month, day, build, revision] = parts[..] else {
+            return Err(invalid());
+        };
+
+        Ok(Self {
+            year: year.parse().map_err(|_| invalid())?,
+            month: month.parse().map_err(|_| invalid())?,
+            day: day.parse().map_err(|_| invalid())?,
+            build: build.parse().map_err(|_| invalid())?,
+            revision: revision.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+impl Display for GameVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:04}.{:02}.{:02}.{:04}.{:04}",
+            self.year, self.month, self.day, self.build, self.revision
+        )
+    }
+}
+
+//////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_display() {
+        let version = "2024.07.23.0000.0000\r\n".parse::<GameVersion>().unwrap();
+        assert_eq!(
+            version,
+            GameVersion {
+                year: 2024,
+                month: 7,
+                day: 23,
+                build: 0,
+                revision: 0,
+            }
+        );
+        assert_eq!(version.to_string(), "2024.07.23.0000.0000");
+
+        let version = "2013.8.27.1.20".parse::<GameVersion>().unwrap();
+        assert_eq!(version.to_string(), "2013.08.27.0001.0020");
+    }
+
+    #[test]
+    fn parse_malformed() {
+        for version in [
+            "",
+            "2024.07.23.0000",
+            "2024.07.23.0000.0000.0000",
+            "2024.07.23.0000.000a",
+            "2024.07.23..0000",
+            "2024.300.23.0000.0000",
+            "-2024.07.23.0000.0000",
+        ] {
+            assert!(
+                matches!(
+                    version.parse::<GameVersion>(),
+                    Err(Error::InvalidVersion(_))
+                ),
+                "{:?}",
+                version
+            );
+        }
+    }
+
+    #[test]
+    fn ordering() {
+        let versions = [
+            "2023.12.31.9999.9999",
+            "2024.01.01.0000.0000",
+            "2024.01.01.0000.0001",
+            "2024.01.01.0001.0000",
+            "2024.01.02.0000.0000",
+            "2024.02.01.0000.0000",
+        ]
+        .map(|version| version.parse::<GameVersion>().unwrap());
+        assert!(versions.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+}
@@ -0,0 +1,235 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use byteorder::{LittleEndian, WriteBytesExt};
+
+use crate::{error::Error, overlay::is_contained_path, sqpack::PlatformId};
+
+use super::{
+    sqpk::expansion_folder, ApplyOption, SqpkCommand, SqpkFileCommand, SqpkFileKind,
+    SqpkFileOperation, ZiPatchChunk, ZiPatchReader,
+};
+
+//////////////////////////////////////////
+
+/// Files left in place by `RemoveAll`, which the launcher keeps track of itself.
+const KEPT_EXTENSIONS: [&str; 1] = ["var"];
+
+/// The base game's opening movies, which `RemoveAll` leaves in `movie/ffxiv`.
+const KEPT_MOVIES: [&str; 4] = ["00000.bk2", "00001.bk2", "00002.bk2", "00003.bk2"];
+
+/// Zeroes are written in pieces of this size, rather than all at once.
+const ZERO_BUFFER_SIZE: usize = 0x10000;
+
+//////////////////////////////////////////
+
+/// Applies ZiPatch files to a game directory, the one holding `sqpack`. Patches must be
+/// applied in order, starting from a matching installation (or an empty directory).
+pub struct ZiPatchApplier {
+    game_path: PathBuf,
+    platform: PlatformId,
+    ignore_missing: bool,
+}
+
+impl ZiPatchApplier {
+    /// The platform defaults to Win32, until a patch's `TargetInfo` says otherwise.
+    pub fn new(game_path: impl AsRef<Path>) -> Self {
+        Self {
+            game_path: game_path.as_ref().to_path_buf(),
+            platform: PlatformId::Win32,
+            ignore_missing: false,
+        }
+    }
+
+    pub fn platform(&self) -> PlatformId {
+        self.platform
+    }
+
+    pub fn apply_file(&mut self, patch_path: impl AsRef<Path>) -> Result<(), Error> {
+        let patch_path = patch_path.as_ref();
+        ZiPatchReader::from_file(patch_path)
+            .and_then(|reader| self.apply(reader))
+            .map_err(|e| e.with_path(patch_path.to_string_lossy()))
+    }
+
+    /// Applies every chunk, stopping at the first error. Files changed by earlier chunks are
+    /// left as they are.
+    pub fn apply<R: Read>(&mut self, reader: ZiPatchReader<R>) -> Result<(), Error> {
+        for chunk in reader {
+            self.apply_chunk(&chunk?)?;
+        }
+        Ok(())
+    }
+
+    pub fn apply_chunk(&mut self, chunk: &ZiPatchChunk) -> Result<(), Error> {
+        match chunk {
+            ZiPatchChunk::ApplyOption(option, value) => match option {
+                ApplyOption::IgnoreMissing => self.ignore_missing = *value,
+                // Old data is never checked, so there are no mismatches to ignore
+                ApplyOption::IgnoreOldMismatch => {}
+            },
+            ZiPatchChunk::AddDirectory(name) => {
+                std::fs::create_dir_all(self.resolve(name)?)?;
+            }
+            ZiPatchChunk::DeleteDirectory(name) => {
+                self.ignoring_missing(std::fs::remove_dir(self.resolve(name)?))?;
+            }
+            ZiPatchChunk::Sqpk(command) => self.apply_sqpk(command)?,
+            ZiPatchChunk::FileHeader(_)
+            | ZiPatchChunk::ApplyFreeSpace
+            | ZiPatchChunk::EndOfFile => {}
+        }
+        Ok(())
+    }
+
+    fn apply_sqpk(&mut self, command: &SqpkCommand) -> Result<(), Error> {
+        match command {
+            SqpkCommand::AddData {
+                target,
+                offset,
+                data,
+                delete_size,
+            } => {
+                let mut file = self.open(&target.dat_path(self.platform))?;
+                file.seek(SeekFrom::Start(*offset))?;
+                file.write_all(data)?;
+                write_zeroes(&mut file, *delete_size)?;
+            }
+            SqpkCommand::DeleteData {
+                target,
+                offset,
+                block_count,
+            }
+            | SqpkCommand::ExpandData {
+                target,
+                offset,
+                block_count,
+            } => {
+                let mut file = self.open(&target.dat_path(self.platform))?;
+                write_empty_block(&mut file, *offset, *block_count)?;
+            }
+            SqpkCommand::Header {
+                file_kind,
+                header_kind,
+                target,
+                header,
+            } => {
+                let path = match file_kind {
+                    SqpkFileKind::Dat => target.dat_path(self.platform),
+                    SqpkFileKind::Index => target.index_path(self.platform),
+                };
+                let mut file = self.open(&path)?;
+                file.seek(SeekFrom::Start(header_kind.offset()))?;
+                file.write_all(header)?;
+            }
+            SqpkCommand::File(command) => self.apply_file_command(command)?,
+            SqpkCommand::TargetInfo { platform, .. } => self.platform = *platform,
+            SqpkCommand::Index { .. } | SqpkCommand::PatchInfo { .. } => {}
+        }
+        Ok(())
+    }
+
+    fn apply_file_command(&mut self, command: &SqpkFileCommand) -> Result<(), Error> {
+        let file_path = self.resolve(&command.path)?;
+        match command.operation {
+            SqpkFileOperation::AddFile => {
+                let mut file = self.open(&command.path)?;
+                if command.offset == 0 {
+                    file.set_len(0)?;
+                }
+                file.seek(SeekFrom::Start(command.offset))?;
+                file.write_all(&command.data)?;
+            }
+            SqpkFileOperation::DeleteFile => {
+                self.ignoring_missing(std::fs::remove_file(file_path))?;
+            }
+            SqpkFileOperation::MakeDirTree => std::fs::create_dir_all(file_path)?,
+            SqpkFileOperation::RemoveAll => {
+                let expansion = expansion_folder(command.expansion_id);
+                for folder in ["sqpack", "movie"] {
+                    let folder_path = self.game_path.join(folder).join(&expansion);
+                    if !folder_path.is_dir() {
+                        continue;
+                    }
+                    for entry in std::fs::read_dir(folder_path)? {
+                        let entry_path = entry?.path();
+                        if entry_path.is_file() && !is_kept(folder, &expansion, &entry_path) {
+                            std::fs::remove_file(entry_path)?;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Opens a file for writing, creating it & its directory if needed.
+    fn open(&self, relative_path: &str) -> Result<File, Error> {
+        let file_path = self.resolve(relative_path)?;
+        if let Some(parent) = file_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        Ok(OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(file_path)?)
+    }
+
+    /// The path of a file below the game directory. Patches can't reach outside of it, so
+    /// absolute paths & `..` components are rejected.
+    fn resolve(&self, relative_path: &str) -> Result<PathBuf, Error> {
+        if !is_contained_path(relative_path) {
+            return Err(Error::unknown_value("patch path", relative_path));
+        }
+        Ok(self.game_path.join(relative_path))
+    }
+
+    fn ignoring_missing(&self, result: std::io::Result<()>) -> Result<(), Error> {
+        match result {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && self.ignore_missing => Ok(()),
+            result => Ok(result?),
+        }
+    }
+}
+
+/// Whether `RemoveAll` leaves a file of `{folder}/{expansion}` in place.
+fn is_kept(folder: &str, expansion: &str, file_path: &Path) -> bool {
+    let has_kept_extension = file_path
+        .extension()
+        .is_some_and(|extension| KEPT_EXTENSIONS.iter().any(|kept| extension == *kept));
+    let is_kept_movie = folder == "movie"
+        && expansion == "ffxiv"
+        && file_path
+            .file_name()
+            .is_some_and(|file_name| KEPT_MOVIES.iter().any(|kept| file_name == *kept));
+    has_kept_extension || is_kept_movie
+}
+
+fn write_zeroes(file: &mut File, mut count: u64) -> Result<(), Error> {
+    let zeroes = [0; ZERO_BUFFER_SIZE];
+    while count > 0 {
+        let length = count.min(ZERO_BUFFER_SIZE as u64) as usize;
+        file.write_all(&zeroes[..length])?;
+        count -= length as u64;
+    }
+    Ok(())
+}
+
+/// Clears `block_count` 128-byte blocks, then marks them as a single empty entry.
+fn write_empty_block(file: &mut File, offset: u64, block_count: u32) -> Result<(), Error> {
+    file.seek(SeekFrom::Start(offset))?;
+    write_zeroes(file, (block_count as u64) << 7)?;
+
+    file.seek(SeekFrom::Start(offset))?;
+    file.write_u32::<LittleEndian>(1 << 7)?;
+    file.write_u32::<LittleEndian>(0)?;
+    file.write_u32::<LittleEndian>(0)?;
+    file.write_u32::<LittleEndian>(block_count.saturating_sub(1))?;
+    file.write_u32::<LittleEndian>(0)?;
+    Ok(())
+}
@@ -0,0 +1,192 @@
+use std::{
+    fs::File,
+    io::{BufReader, Cursor, Read},
+    path::Path,
+};
+
+use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
+
+use crate::error::Error;
+
+use super::{sqpk::read_bytes, SqpkCommand};
+
+//////////////////////////////////////////
+
+/// `\x91ZIPATCH\r\n\x1A\n`
+const ZIPATCH_MAGIC: [u8; 12] = [
+    0x91, 0x5A, 0x49, 0x50, 0x41, 0x54, 0x43, 0x48, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+
+type Crc32 = crc::Crc<u32>;
+static CRC: Crc32 = Crc32::new(&crc::CRC_32_ISO_HDLC);
+
+//////////////////////////////////////////
+
+/// Reads the chunks of a ZiPatch file one at a time, as patches can be several GB.
+pub struct ZiPatchReader<R> {
+    reader: R,
+    finished: bool,
+}
+
+impl ZiPatchReader<BufReader<File>> {
+    pub fn from_file(file_path: impl AsRef<Path>) -> Result<Self, Error> {
+        let file_path = file_path.as_ref();
+        let reader = BufReader::new(File::open(file_path)?);
+        Self::new(reader).map_err(|e| e.with_path(file_path.to_string_lossy()))
+    }
+}
+
+impl<R: Read> ZiPatchReader<R> {
+    pub fn new(mut reader: R) -> Result<Self, Error> {
+        let mut magic = [0; ZIPATCH_MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if magic != ZIPATCH_MAGIC {
+            return Err(Error::InvalidMagic {
+                kind: "ZiPatch",
+                path: None,
+            });
+        }
+
+        Ok(Self {
+            reader,
+            finished: false,
+        })
+    }
+}
+
+impl<R: Read> Iterator for ZiPatchReader<R> {
+    type Item = Result<ZiPatchChunk, Error>;
+
+    /// Returns chunks up to & including `EndOfFile`.
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        let chunk = ZiPatchChunk::from_reader(&mut self.reader);
+        self.finished = matches!(chunk, Ok(ZiPatchChunk::EndOfFile) | Err(_));
+        Some(chunk)
+    }
+}
+
+//////////////////////////////////////////
+
+#[derive(Debug)]
+pub enum ZiPatchChunk {
+    /// `FHDR`
+    FileHeader(FileHeaderChunk),
+    /// `APLY`
+    ApplyOption(ApplyOption, bool),
+    /// `APFS`, the free space needed to apply the patch.
+    ApplyFreeSpace,
+    /// `ADIR`
+    AddDirectory(String),
+    /// `DELD`
+    DeleteDirectory(String),
+    /// `SQPK`
+    Sqpk(SqpkCommand),
+    /// `EOF_`
+    EndOfFile,
+}
+
+impl ZiPatchChunk {
+    /// Reads a chunk, checking its CRC-32.
+    pub fn from_reader(reader: &mut impl ReadBytesExt) -> Result<Self, Error> {
+        let size = reader.read_u32::<BigEndian>()?;
+        let mut chunk_type = [0; 4];
+        reader.read_exact(&mut chunk_type)?;
+
+        let mut payload = Vec::new();
+        let read = (&mut *reader).take(size as u64).read_to_end(&mut payload)?;
+        if read != size as usize {
+            return Err(Error::Truncated { path: None });
+        }
+
+        let crc = reader.read_u32::<BigEndian>()?;
+        let mut digest = CRC.digest();
+        digest.update(&chunk_type);
+        digest.update(&payload);
+        if digest.finalize() != crc {
+            return Err(Error::InvalidChecksum {
+                kind: "ZiPatch chunk",
+                path: None,
+            });
+        }
+
+        let mut payload = Cursor::new(payload);
+        Ok(match &chunk_type {
+            b"FHDR" => ZiPatchChunk::FileHeader(FileHeaderChunk::from_reader(&mut payload)?),
+            b"APLY" => {
+                let option = payload.read_u32::<BigEndian>()?;
+                let _size = payload.read_u32::<BigEndian>()?;
+                let value = payload.read_u32::<BigEndian>()? != 0;
+                ZiPatchChunk::ApplyOption(ApplyOption::try_from(option)?, value)
+            }
+            b"APFS" => ZiPatchChunk::ApplyFreeSpace,
+            b"ADIR" => ZiPatchChunk::AddDirectory(read_name(&mut payload)?),
+            b"DELD" => ZiPatchChunk::DeleteDirectory(read_name(&mut payload)?),
+            b"SQPK" => ZiPatchChunk::Sqpk(SqpkCommand::from_reader(&mut payload)?),
+            b"EOF_" => ZiPatchChunk::EndOfFile,
+            _ => {
+                return Err(Error::unknown_value(
+                    "ZiPatch chunk",
+                    String::from_utf8_lossy(&chunk_type),
+                ))
+            }
+        })
+    }
+}
+
+fn read_name(reader: &mut impl ReadBytesExt) -> Result<String, Error> {
+    let length = reader.read_u32::<BigEndian>()?;
+    let name = read_bytes(reader, length as u64)?;
+    Ok(String::from_utf8(name)?)
+}
+
+//////////////////////////////////////////
+
+#[derive(Debug)]
+pub struct FileHeaderChunk {
+    pub version: u8,
+    /// `DIFF` for incremental patches, `HIST` for full ones.
+    pub patch_type: String,
+    pub entry_files: u32,
+}
+
+impl FileHeaderChunk {
+    pub fn from_reader(reader: &mut impl ReadBytesExt) -> Result<Self, Error> {
+        // The version is the third byte of a little-endian u32
+        let version = (reader.read_u32::<LittleEndian>()? >> 16) as u8;
+        let mut patch_type = [0; 4];
+        reader.read_exact(&mut patch_type)?;
+        let entry_files = reader.read_u32::<BigEndian>()?;
+
+        Ok(Self {
+            version,
+            patch_type: String::from_utf8_lossy(&patch_type).to_string(),
+            entry_files,
+        })
+    }
+}
+
+//////////////////////////////////////////
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApplyOption {
+    /// Skip operations on files & directories which don't exist.
+    IgnoreMissing,
+    /// Skip operations whose expected old data doesn't match.
+    IgnoreOldMismatch,
+}
+
+impl TryFrom<u32> for ApplyOption {
+    type Error = Error;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        Ok(match value {
+            1 => ApplyOption::IgnoreMissing,
+            2 => ApplyOption::IgnoreOldMismatch,
+            _ => return Err(Error::unknown_value("ApplyOption", value)),
+        })
+    }
+}
@@ -0,0 +1,9 @@
+mod apply;
+mod chunk;
+mod sqpk;
+
+pub use apply::ZiPatchApplier;
+pub use chunk::{ApplyOption, FileHeaderChunk, ZiPatchChunk, ZiPatchReader};
+pub use sqpk::{
+    SqpkCommand, SqpkFileCommand, SqpkFileKind, SqpkFileOperation, SqpkHeaderKind, SqpkTargetFile,
+};
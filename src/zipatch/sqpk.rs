@@ -0,0 +1,411 @@
+use std::io::Read;
+
+use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
+use flate2::read::DeflateDecoder;
+
+use crate::{error::Error, file_key::REPOSITORIES, sqpack::PlatformId};
+
+//////////////////////////////////////////
+
+/// Blocks stored without compression carry this value in place of their compressed size.
+const UNCOMPRESSED_BLOCK_SIZE: u32 = 32000;
+
+//////////////////////////////////////////
+
+/// A single operation of a `SQPK` chunk. Block offsets & sizes are in bytes.
+#[derive(Debug)]
+pub enum SqpkCommand {
+    /// `A`: writes data into a `.datN` file, then zeroes the following `delete_size` bytes.
+    AddData {
+        target: SqpkTargetFile,
+        offset: u64,
+        data: Box<[u8]>,
+        delete_size: u64,
+    },
+    /// `D`: replaces entries with an empty block.
+    DeleteData {
+        target: SqpkTargetFile,
+        offset: u64,
+        block_count: u32,
+    },
+    /// `E`: reserves space with an empty block.
+    ExpandData {
+        target: SqpkTargetFile,
+        offset: u64,
+        block_count: u32,
+    },
+    /// `H`: replaces one of the 0x400-byte headers of a `.datN` or `.index` file.
+    Header {
+        file_kind: SqpkFileKind,
+        header_kind: SqpkHeaderKind,
+        target: SqpkTargetFile,
+        header: Box<[u8]>,
+    },
+    /// `F`: operates on a whole file, relative to the game directory.
+    File(SqpkFileCommand),
+    /// `I`: adds or removes an index entry. The `.index` files are also patched directly, so
+    /// these don't need applying.
+    Index {
+        is_add: bool,
+        is_synonym: bool,
+        target: SqpkTargetFile,
+        file_hash: u64,
+        offset: u32,
+        block_number: u32,
+    },
+    /// `X`
+    PatchInfo {
+        status: u8,
+        version: u8,
+        install_size: u64,
+    },
+    /// `T`
+    TargetInfo {
+        platform: PlatformId,
+        region: i16,
+        is_debug: bool,
+        version: u16,
+    },
+}
+
+impl SqpkCommand {
+    pub fn from_reader(reader: &mut impl ReadBytesExt) -> Result<Self, Error> {
+        let _size = reader.read_u32::<BigEndian>()?;
+        let command = reader.read_u8()?;
+
+        Ok(match command {
+            b'A' => {
+                skip(reader, 3)?;
+                let target = SqpkTargetFile::from_reader(reader)?;
+                let offset = read_blocks(reader)?;
+                let size = read_blocks(reader)?;
+                let delete_size = read_blocks(reader)?;
+
+                let data = read_bytes(reader, size)?;
+                SqpkCommand::AddData {
+                    target,
+                    offset,
+                    data: data.into_boxed_slice(),
+                    delete_size,
+                }
+            }
+            b'D' | b'E' => {
+                skip(reader, 3)?;
+                let target = SqpkTargetFile::from_reader(reader)?;
+                let offset = read_blocks(reader)?;
+                let block_count = reader.read_u32::<BigEndian>()?;
+                let _reserved = reader.read_u32::<BigEndian>()?;
+
+                if command == b'D' {
+                    SqpkCommand::DeleteData {
+                        target,
+                        offset,
+                        block_count,
+                    }
+                } else {
+                    SqpkCommand::ExpandData {
+                        target,
+                        offset,
+                        block_count,
+                    }
+                }
+            }
+            b'H' => {
+                let file_kind = SqpkFileKind::try_from(reader.read_u8()?)?;
+                let header_kind = SqpkHeaderKind::try_from(reader.read_u8()?)?;
+                skip(reader, 1)?;
+                let target = SqpkTargetFile::from_reader(reader)?;
+
+                let mut header = vec![0; 0x400];
+                reader.read_exact(&mut header)?;
+                SqpkCommand::Header {
+                    file_kind,
+                    header_kind,
+                    target,
+                    header: header.into_boxed_slice(),
+                }
+            }
+            b'F' => SqpkCommand::File(SqpkFileCommand::from_reader(reader)?),
+            b'I' => {
+                let is_add = reader.read_u8()? == b'A';
+                let is_synonym = reader.read_u8()? != 0;
+                skip(reader, 1)?;
+                let target = SqpkTargetFile::from_reader(reader)?;
+                let file_hash = reader.read_u64::<BigEndian>()?;
+                let offset = reader.read_u32::<BigEndian>()?;
+                let block_number = reader.read_u32::<BigEndian>()?;
+
+                SqpkCommand::Index {
+                    is_add,
+                    is_synonym,
+                    target,
+                    file_hash,
+                    offset,
+                    block_number,
+                }
+            }
+            b'X' => {
+                let status = reader.read_u8()?;
+                let version = reader.read_u8()?;
+                skip(reader, 1)?;
+                let install_size = reader.read_u64::<BigEndian>()?;
+
+                SqpkCommand::PatchInfo {
+                    status,
+                    version,
+                    install_size,
+                }
+            }
+            b'T' => {
+                skip(reader, 3)?;
+                let platform = reader.read_u16::<BigEndian>()?;
+                let region = reader.read_i16::<BigEndian>()?;
+                let is_debug = reader.read_i16::<BigEndian>()? != 0;
+                let version = reader.read_u16::<BigEndian>()?;
+
+                let platform = u8::try_from(platform)
+                    .map_err(|_| Error::unknown_value("PlatformId", platform))
+                    .and_then(PlatformId::try_from)?;
+                SqpkCommand::TargetInfo {
+                    platform,
+                    region,
+                    is_debug,
+                    version,
+                }
+            }
+            _ => return Err(Error::unknown_value("SqpkCommand", command as char)),
+        })
+    }
+}
+
+/// Offsets & sizes in `A`, `D` & `E` commands are stored in 128-byte units.
+fn read_blocks(reader: &mut impl ReadBytesExt) -> Result<u64, Error> {
+    Ok((reader.read_u32::<BigEndian>()? as u64) << 7)
+}
+
+fn skip(reader: &mut impl ReadBytesExt, count: usize) -> Result<(), Error> {
+    let mut buf = [0; 4];
+    reader.read_exact(&mut buf[..count])?;
+    Ok(())
+}
+
+/// Reads `length` bytes, which must all be left in the chunk. Lengths come from the patch, so
+/// nothing is allocated up front.
+pub(super) fn read_bytes(reader: &mut impl Read, length: u64) -> Result<Vec<u8>, Error> {
+    let mut bytes = Vec::new();
+    if reader.take(length).read_to_end(&mut bytes)? as u64 != length {
+        return Err(Error::Truncated { path: None });
+    }
+    Ok(bytes)
+}
+
+//////////////////////////////////////////
+
+/// A SqPack file of the game, as `{category}{repository}{chunk}.{platform}.{extension}`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SqpkTargetFile {
+    pub main_id: u16,
+    /// The repository in the high byte & the chunk in the low byte.
+    pub sub_id: u16,
+    /// The `N` of `.datN`, or of `.indexN` (with `0` meaning `.index`).
+    pub file_id: u32,
+}
+
+impl SqpkTargetFile {
+    pub fn from_reader(reader: &mut impl ReadBytesExt) -> Result<Self, Error> {
+        let main_id = reader.read_u16::<BigEndian>()?;
+        let sub_id = reader.read_u16::<BigEndian>()?;
+        let file_id = reader.read_u32::<BigEndian>()?;
+
+        Ok(Self {
+            main_id,
+            sub_id,
+            file_id,
+        })
+    }
+
+    /// Path of the `.datN` file, relative to the game directory.
+    pub fn dat_path(&self, platform: PlatformId) -> String {
+        format!("{}.dat{}", self.base_path(platform), self.file_id)
+    }
+
+    /// Path of the `.index` or `.indexN` file, relative to the game directory.
+    pub fn index_path(&self, platform: PlatformId) -> String {
+        match self.file_id {
+            0 => format!("{}.index", self.base_path(platform)),
+            file_id => format!("{}.index{}", self.base_path(platform), file_id),
+        }
+    }
+
+    fn base_path(&self, platform: PlatformId) -> String {
+        format!(
+            "sqpack/{}/{:02x}{:04x}.{}",
+            expansion_folder(self.sub_id >> 8),
+            self.main_id,
+            self.sub_id,
+            platform.file_extension()
+        )
+    }
+}
+
+/// `ffxiv` for the base game, `exN` for expansions.
+pub(crate) fn expansion_folder(expansion_id: u16) -> String {
+    REPOSITORIES
+        .get(expansion_id as usize)
+        .map(|repository| repository.to_string())
+        .unwrap_or_else(|| format!("ex{}", expansion_id))
+}
+
+//////////////////////////////////////////
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SqpkFileKind {
+    Dat,
+    Index,
+}
+
+impl TryFrom<u8> for SqpkFileKind {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            b'D' => SqpkFileKind::Dat,
+            b'I' => SqpkFileKind::Index,
+            _ => return Err(Error::unknown_value("SqpkFileKind", value as char)),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SqpkHeaderKind {
+    /// The SqPack header, at the start of the file.
+    Version,
+    /// The index header, following the SqPack header.
+    Index,
+    /// The data header, following the SqPack header.
+    Data,
+}
+
+impl SqpkHeaderKind {
+    /// Where the header sits in its file.
+    pub fn offset(&self) -> u64 {
+        match *self {
+            SqpkHeaderKind::Version => 0,
+            SqpkHeaderKind::Index | SqpkHeaderKind::Data => 0x400,
+        }
+    }
+}
+
+impl TryFrom<u8> for SqpkHeaderKind {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            b'V' => SqpkHeaderKind::Version,
+            b'I' => SqpkHeaderKind::Index,
+            b'D' => SqpkHeaderKind::Data,
+            _ => return Err(Error::unknown_value("SqpkHeaderKind", value as char)),
+        })
+    }
+}
+
+//////////////////////////////////////////
+
+#[derive(Debug)]
+pub struct SqpkFileCommand {
+    pub operation: SqpkFileOperation,
+    pub offset: u64,
+    pub size: u64,
+    pub expansion_id: u16,
+    /// Relative to the game directory.
+    pub path: String,
+    /// The decompressed data to write at `offset`, for `AddFile`.
+    pub data: Box<[u8]>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SqpkFileOperation {
+    AddFile,
+    /// Removes every file of the expansion's `sqpack` & `movie` directories.
+    RemoveAll,
+    DeleteFile,
+    MakeDirTree,
+}
+
+impl SqpkFileCommand {
+    pub fn from_reader(reader: &mut impl ReadBytesExt) -> Result<Self, Error> {
+        let operation = reader.read_u8()?;
+        skip(reader, 2)?;
+        let offset = reader.read_u64::<BigEndian>()?;
+        let size = reader.read_u64::<BigEndian>()?;
+        let path_length = reader.read_u32::<BigEndian>()?;
+        let expansion_id = reader.read_u16::<BigEndian>()?;
+        skip(reader, 2)?;
+
+        let mut path = read_bytes(reader, path_length as u64)?;
+        let length = path.iter().position(|&c| c == 0).unwrap_or(path.len());
+        path.truncate(length);
+        let path = String::from_utf8(path)?;
+
+        let operation = match operation {
+            b'A' => SqpkFileOperation::AddFile,
+            b'R' => SqpkFileOperation::RemoveAll,
+            b'D' => SqpkFileOperation::DeleteFile,
+            b'M' => SqpkFileOperation::MakeDirTree,
+            _ => return Err(Error::unknown_value("SqpkFileOperation", operation as char)),
+        };
+
+        // The rest of the chunk holds the file's data, as SqPack-style blocks
+        let mut data = Vec::new();
+        if operation == SqpkFileOperation::AddFile {
+            while let Some(block) = read_block(reader)? {
+                data.extend_from_slice(&block);
+            }
+        }
+
+        Ok(Self {
+            operation,
+            offset,
+            size,
+            expansion_id,
+            path,
+            data: data.into_boxed_slice(),
+        })
+    }
+}
+
+/// Reads & decompresses a block, or returns `None` at the end of the chunk.
+fn read_block(reader: &mut impl ReadBytesExt) -> Result<Option<Vec<u8>>, Error> {
+    let header_size = match reader.read_u32::<LittleEndian>() {
+        Ok(header_size) => header_size,
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let _padding = reader.read_u32::<LittleEndian>()?;
+    let compressed_size = reader.read_u32::<LittleEndian>()?;
+    let decompressed_size = reader.read_u32::<LittleEndian>()?;
+
+    let is_uncompressed = compressed_size == UNCOMPRESSED_BLOCK_SIZE;
+    let data_size = if is_uncompressed {
+        decompressed_size
+    } else {
+        compressed_size
+    };
+    let data = read_bytes(reader, data_size as u64)?;
+
+    let block = if is_uncompressed {
+        data
+    } else {
+        let mut block = Vec::new();
+        DeflateDecoder::new(&data[..]).read_to_end(&mut block)?;
+        block
+    };
+
+    // Blocks are padded to 128 bytes, though the chunk may end before the last one's padding.
+    // Both sizes come from the patch, so they're added without overflowing
+    let block_size = header_size as u64 + data_size as u64;
+    let padding = block_size.next_multiple_of(0x80) - block_size;
+    std::io::copy(&mut (&mut *reader).take(padding), &mut std::io::sink())?;
+
+    Ok(Some(block))
+}
@@ -0,0 +1,206 @@
+// Each test crate uses its own share of these fixtures
+#![allow(dead_code)]
+
+use std::path::Path;
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian, WriteBytesExt};
+use ffxiv_parser_lib::{
+    FfxivLibrary, MemorySource, PlatformId, SqPackIndexTableEntry, SqPackWriter,
+};
+
+//////////////////////////////////////////
+
+/// Platforms of both byte orders, which tests run over in turn.
+pub const PLATFORMS: [PlatformId; 2] = [PlatformId::Win32, PlatformId::PS3];
+
+pub const TEXTURE_PATH: &str = "chara/equipment/e0001/texture/v01_c0101e0001_top_d.tex";
+pub const MODEL_PATH: &str = "chara/equipment/e0001/model/c0101e0001_top.mdl";
+
+/// Entry types, from the common header of each `.datN` entry.
+pub const STANDARD_ENTRY: u32 = 2;
+pub const MODEL_ENTRY: u32 = 3;
+pub const TEXTURE_ENTRY: u32 = 4;
+
+/// Bytes which don't repeat too quickly, so blocks stay large once compressed.
+pub fn noise(length: usize, seed: u32) -> Vec<u8> {
+    (0..length as u32)
+        .map(|i| (i.wrapping_add(seed).wrapping_mul(2654435761) >> 13) as u8)
+        .collect()
+}
+
+/// `count` files of noise, each `length` bytes long & named `exd/noise{i}.bin`.
+pub fn noise_files(count: u32, length: usize) -> Vec<(String, Vec<u8>)> {
+    (0..count)
+        .map(|i| (format!("exd/noise{}.bin", i), noise(length, i)))
+        .collect()
+}
+
+/// A 64x64 texture with three mip levels, each stored as its own LOD.
+pub fn texture<E: ByteOrder>() -> Vec<u8> {
+    let mip_sizes = [40000, 10000, 2500];
+
+    let mut texture = Vec::new();
+    texture.write_u32::<E>(0).unwrap();
+    texture.write_u32::<E>(0x1450).unwrap();
+    texture.write_u16::<E>(64).unwrap();
+    texture.write_u16::<E>(64).unwrap();
+    texture.write_u16::<E>(1).unwrap();
+    texture.write_u16::<E>(mip_sizes.len() as u16).unwrap();
+    for _ in 0..3 {
+        texture.write_u32::<E>(0).unwrap();
+    }
+    let mut offset = 0x50;
+    for i in 0..13 {
+        match mip_sizes.get(i) {
+            Some(size) => {
+                texture.write_u32::<E>(offset).unwrap();
+                offset += size;
+            }
+            None => texture.write_u32::<E>(0).unwrap(),
+        }
+    }
+    for (i, size) in mip_sizes.into_iter().enumerate() {
+        texture.extend(noise(size as usize, i as u32));
+    }
+    texture
+}
+
+/// A model with two LODs, whose vertex & index buffers are split into several blocks.
+pub fn model<E: ByteOrder>() -> Vec<u8> {
+    let stack_size = 300;
+    let runtime_size = 20000;
+    let vertex_sizes = [33000, 500, 0];
+    let index_sizes = [1000, 100, 0];
+
+    let mut vertex_offsets = [0; 3];
+    let mut index_offsets = [0; 3];
+    let mut offset = 0x44 + stack_size + runtime_size;
+    for lod in 0..2 {
+        vertex_offsets[lod] = offset;
+        offset += vertex_sizes[lod];
+        index_offsets[lod] = offset;
+        offset += index_sizes[lod];
+    }
+
+    let mut model = Vec::new();
+    model.write_u32::<E>(0x01000005).unwrap();
+    model.write_u32::<E>(stack_size).unwrap();
+    model.write_u32::<E>(runtime_size).unwrap();
+    model.write_u16::<E>(3).unwrap();
+    model.write_u16::<E>(2).unwrap();
+    let fields = vertex_offsets
+        .iter()
+        .chain(&index_offsets)
+        .chain(&vertex_sizes)
+        .chain(&index_sizes);
+    for field in fields {
+        model.write_u32::<E>(*field).unwrap();
+    }
+    model.extend([2, 0, 0, 0]);
+    model.extend(noise(offset as usize - 0x44, 7));
+    model
+}
+
+/// Files of each kind, across two indexes & an expansion.
+pub fn fixture_files(platform: PlatformId) -> Vec<(&'static str, Vec<u8>)> {
+    let (texture, model) = if platform.is_big_endian() {
+        (texture::<BigEndian>(), model::<BigEndian>())
+    } else {
+        (texture::<LittleEndian>(), model::<LittleEndian>())
+    };
+    vec![
+        ("exd/root.exl", b"EXLT,2\nItem,0\n".to_vec()),
+        (
+            "exd/big.bin",
+            (0..100_000u32).map(|i| (i % 97) as u8).collect(),
+        ),
+        ("exd/noise.bin", noise(20_000, 1)),
+        ("exd/empty.bin", Vec::new()),
+        (TEXTURE_PATH, texture),
+        (MODEL_PATH, model),
+        ("bg/ex1/01_xxx/level/planevent.lgb", vec![9; 50]),
+    ]
+}
+
+/// Writes `fixture_files` below `sqpack_path`.
+pub fn write_fixture(sqpack_path: &Path, platform: PlatformId) -> Vec<(&'static str, Vec<u8>)> {
+    let files = fixture_files(platform);
+    let mut writer = SqPackWriter::new(platform);
+    for (path, contents) in &files {
+        writer.add_file(path, contents.clone()).unwrap();
+    }
+    writer.write(sqpack_path).unwrap();
+    files
+}
+
+/// Every file below `path`, by its path relative to `path`.
+pub fn files_below(path: &Path) -> Vec<(String, Vec<u8>)> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(path).unwrap() {
+        let entry_path = entry.unwrap().path();
+        let name = entry_path
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .to_string();
+        if entry_path.is_dir() {
+            for (child, contents) in files_below(&entry_path) {
+                files.push((format!("{}/{}", name, child), contents));
+            }
+        } else {
+            files.push((name, std::fs::read(&entry_path).unwrap()));
+        }
+    }
+    files.sort();
+    files
+}
+
+/// A source holding a copy of every file below `sqpack_path`.
+pub fn copy_to_memory(sqpack_path: &Path) -> MemorySource {
+    let mut source = MemorySource::new();
+    for (name, contents) in files_below(sqpack_path) {
+        source.insert(name, contents);
+    }
+    source
+}
+
+/// Asserts that every file reads back whole, both at once & through a stream.
+pub fn assert_files(library: &FfxivLibrary, files: &[(&str, Vec<u8>)]) {
+    for (path, contents) in files {
+        let file = library.get_file(path).unwrap();
+        assert_eq!(&file[..], &contents[..], "{}", path);
+
+        let mut streamed = Vec::new();
+        std::io::Read::read_to_end(&mut library.open(path).unwrap(), &mut streamed).unwrap();
+        assert_eq!(&streamed, contents, "{}", path);
+    }
+}
+
+/// The index entry of a file, with the `.datN` file it points into.
+pub fn entry(library: &FfxivLibrary, path: &str) -> (String, SqPackIndexTableEntry) {
+    for (file_key, index) in library.indexes().unwrap() {
+        if let Some(entry) = index.entry_from_path(path) {
+            let dat_path = format!(
+                "{}/{}.{}.dat{}",
+                file_key.repository,
+                file_key,
+                library.platform().file_extension(),
+                entry.data_file_id
+            );
+            return (dat_path, *entry);
+        }
+    }
+    panic!("{} isn't in any index", path);
+}
+
+/// The type of a file's entry, as stored in its `.datN` file.
+pub fn entry_type(sqpack_path: &Path, library: &FfxivLibrary, path: &str) -> u32 {
+    let (dat_path, entry) = entry(library, path);
+    let dat = std::fs::read(sqpack_path.join(dat_path)).unwrap();
+    let field = &dat[entry.offset as usize + 4..entry.offset as usize + 8];
+    if library.platform().is_big_endian() {
+        BigEndian::read_u32(field)
+    } else {
+        LittleEndian::read_u32(field)
+    }
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use ffxiv_parser_lib::{verify_sqpack_file, Error, FfxivLibrary, VerifyProblem};
+
+use common::{entry, write_fixture, PLATFORMS};
+
+//////////////////////////////////////////
+
+#[test]
+fn header_size_past_the_dat_file() {
+    for platform in PLATFORMS {
+        let dir = tempfile::tempdir().unwrap();
+        write_fixture(dir.path(), platform);
+
+        let library = FfxivLibrary::with_platform(dir.path(), platform);
+        let (dat_path, entry) = entry(&library, "exd/root.exl");
+        let dat_path = dir.path().join(dat_path);
+        let mut dat = std::fs::read(&dat_path).unwrap();
+        let offset = entry.offset as usize;
+        dat[offset..offset + 4].fill(0xFF);
+        std::fs::write(&dat_path, dat).unwrap();
+
+        for memory_mapped in [false, true] {
+            let mut library = FfxivLibrary::with_platform(dir.path(), platform);
+            library.set_memory_mapped(memory_mapped);
+            assert!(matches!(
+                library.get_file("exd/root.exl"),
+                Err(Error::Truncated { .. })
+            ));
+        }
+    }
+}
+
+#[test]
+fn dat_data_digests() {
+    for platform in PLATFORMS {
+        let dir = tempfile::tempdir().unwrap();
+        write_fixture(dir.path(), platform);
+
+        let library = FfxivLibrary::with_platform(dir.path(), platform);
+        let (dat_path, entry) = entry(&library, "exd/root.exl");
+        let dat_path = dir.path().join(dat_path);
+        assert!(verify_sqpack_file(&dat_path).unwrap().is_ok());
+        let original = std::fs::read(&dat_path).unwrap();
+
+        // A changed byte within an entry
+        let mut dat = original.clone();
+        dat[entry.offset as usize + 0x80] ^= 0xFF;
+        std::fs::write(&dat_path, dat).unwrap();
+        let report = verify_sqpack_file(&dat_path).unwrap();
+        assert!(matches!(
+            report.problems[..],
+            [VerifyProblem::DigestMismatch {
+                section: "data",
+                ..
+            }]
+        ));
+
+        // A zeroed data digest, in a header whose own digest is still valid
+        let mut dat = original.clone();
+        dat[0x428..0x43C].fill(0);
+        let header_digest = sha1_smol::Sha1::from(&dat[0x400..0x7C0]).digest().bytes();
+        dat[0x7C0..0x7D4].copy_from_slice(&header_digest);
+        std::fs::write(&dat_path, dat).unwrap();
+        let report = verify_sqpack_file(&dat_path).unwrap();
+        assert!(matches!(
+            report.problems[..],
+            [VerifyProblem::MissingDigest {
+                section: "data",
+                ..
+            }]
+        ));
+    }
+}
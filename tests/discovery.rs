@@ -0,0 +1,140 @@
+use std::path::Path;
+
+use ffxiv_parser_lib::{GameInstallation, PlatformId};
+
+//////////////////////////////////////////
+
+const VERSION: &str = "2024.07.23.0000.0000";
+
+/// Creates the files `GameInstallation` looks for: the version file & the base game's index.
+fn write_game(game_path: &Path, platform: PlatformId) {
+    let repository_path = game_path.join("sqpack/ffxiv");
+    std::fs::create_dir_all(&repository_path).unwrap();
+    std::fs::write(game_path.join("ffxivgame.ver"), VERSION).unwrap();
+    std::fs::write(
+        repository_path.join(format!("0a0000.{}.index", platform.file_extension())),
+        [],
+    )
+    .unwrap();
+}
+
+fn game_paths(home: &Path) -> Vec<String> {
+    let mut game_paths = GameInstallation::discover_in(home)
+        .into_iter()
+        .map(|installation| {
+            assert_eq!(installation.version.to_string(), VERSION);
+            let game_path = installation.game_path.strip_prefix(home).unwrap();
+            game_path.to_string_lossy().to_string()
+        })
+        .collect::<Vec<_>>();
+    game_paths.sort();
+    game_paths
+}
+
+//////////////////////////////////////////
+
+#[cfg(unix)]
+#[test]
+fn steam() {
+    let home = tempfile::tempdir().unwrap();
+    let steam_root = home.path().join(".local/share/Steam");
+    write_game(
+        &steam_root.join("steamapps/common/FINAL FANTASY XIV Online/game"),
+        PlatformId::Win32,
+    );
+    std::fs::create_dir_all(home.path().join(".steam")).unwrap();
+    std::os::unix::fs::symlink(&steam_root, home.path().join(".steam/steam")).unwrap();
+
+    // Reachable through both Steam roots, but only found once
+    assert_eq!(
+        game_paths(home.path()),
+        [".steam/steam/steamapps/common/FINAL FANTASY XIV Online/game"]
+    );
+
+    let installation = GameInstallation::discover_in(home.path()).remove(0);
+    assert_eq!(installation.platform, PlatformId::Win32);
+    assert_eq!(
+        installation.sqpack_path(),
+        installation.game_path.join("sqpack")
+    );
+}
+
+#[test]
+fn steam_proton_prefix() {
+    let home = tempfile::tempdir().unwrap();
+    write_game(
+        &home.path().join(
+            ".local/share/Steam/steamapps/compatdata/39210/pfx/drive_c/Program Files (x86)\
+             /SquareEnix/FINAL FANTASY XIV - A Realm Reborn/game",
+        ),
+        PlatformId::Win32,
+    );
+
+    assert_eq!(
+        game_paths(home.path()),
+        [
+            ".local/share/Steam/steamapps/compatdata/39210/pfx/drive_c/Program Files (x86)\
+          /SquareEnix/FINAL FANTASY XIV - A Realm Reborn/game"
+        ]
+    );
+}
+
+#[test]
+fn xivlauncher() {
+    let home = tempfile::tempdir().unwrap();
+    write_game(&home.path().join(".xlcore/ffxiv/game"), PlatformId::Win32);
+    write_game(
+        &home.path().join(
+            ".xlcore/wineprefix/drive_c/Program Files/SquareEnix\
+             /FINAL FANTASY XIV - A Realm Reborn/game",
+        ),
+        PlatformId::Win32,
+    );
+
+    assert_eq!(
+        game_paths(home.path()),
+        [
+            ".xlcore/ffxiv/game",
+            ".xlcore/wineprefix/drive_c/Program Files/SquareEnix\
+             /FINAL FANTASY XIV - A Realm Reborn/game",
+        ]
+    );
+}
+
+#[test]
+fn lutris() {
+    let home = tempfile::tempdir().unwrap();
+    write_game(
+        &home.path().join(
+            "Games/final-fantasy-xiv-online/drive_c/Program Files (x86)/SquareEnix\
+             /FINAL FANTASY XIV - A Realm Reborn/game",
+        ),
+        PlatformId::PS3,
+    );
+
+    let installations = GameInstallation::discover_in(home.path());
+    assert_eq!(installations.len(), 1);
+    assert_eq!(installations[0].platform, PlatformId::PS3);
+    assert!(installations[0]
+        .game_path
+        .starts_with(home.path().join("Games/final-fantasy-xiv-online")));
+}
+
+#[test]
+fn missing_installation() {
+    let home = tempfile::tempdir().unwrap();
+    assert!(GameInstallation::discover_in(home.path()).is_empty());
+    assert!(GameInstallation::discover_in(home.path().join("missing")).is_empty());
+
+    // Without an index
+    let game_path = home.path().join(".xlcore/ffxiv/game");
+    std::fs::create_dir_all(&game_path).unwrap();
+    std::fs::write(game_path.join("ffxivgame.ver"), VERSION).unwrap();
+    assert!(GameInstallation::from_game_directory(&game_path).is_none());
+
+    // With an unparsable version
+    write_game(&game_path, PlatformId::Win32);
+    std::fs::write(game_path.join("ffxivgame.ver"), "latest").unwrap();
+    assert!(GameInstallation::from_game_directory(&game_path).is_none());
+    assert!(GameInstallation::discover_in(home.path()).is_empty());
+}
@@ -0,0 +1,38 @@
+mod common;
+
+use ffxiv_parser_lib::{FfxivLibrary, PlatformId, SqPackWriter};
+
+use common::{assert_files, write_fixture, PLATFORMS};
+
+//////////////////////////////////////////
+
+#[test]
+fn mapped_reads_match_streamed_reads() {
+    for platform in PLATFORMS {
+        let dir = tempfile::tempdir().unwrap();
+        let files = write_fixture(dir.path(), platform);
+
+        let mut library = FfxivLibrary::with_platform(dir.path(), platform);
+        library.set_memory_mapped(true);
+        assert_files(&library, &files);
+    }
+}
+
+#[test]
+fn mapped_reads_of_large_files() {
+    // Past the size which is decompressed straight into one buffer
+    let contents = (0..20_000_000u32)
+        .map(|i| (i / 1000) as u8)
+        .collect::<Vec<_>>();
+    let dir = tempfile::tempdir().unwrap();
+    let mut writer = SqPackWriter::new(PlatformId::Win32);
+    writer.add_file("exd/large.bin", contents.clone()).unwrap();
+    writer.write(dir.path()).unwrap();
+
+    let mut library = FfxivLibrary::with_platform(dir.path(), PlatformId::Win32);
+    library.set_memory_mapped(true);
+    assert_eq!(
+        &library.get_file("exd/large.bin").unwrap()[..],
+        &contents[..]
+    );
+}
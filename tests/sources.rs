@@ -0,0 +1,163 @@
+mod common;
+
+use std::path::Path;
+
+use ffxiv_parser_lib::{DirectorySource, Error, FfxivLibrary, PlatformId, SqPackSource};
+
+use common::{assert_files, copy_to_memory, write_fixture};
+
+//////////////////////////////////////////
+
+const VERSION: &str = "2024.07.23.0000.0000";
+
+/// Writes a game directory, with the fixture in its `sqpack` folder & a movie folder whose
+/// name matches the base game's.
+fn write_game(game_path: &Path, platform: PlatformId) -> Vec<(&'static str, Vec<u8>)> {
+    let files = write_fixture(&game_path.join("sqpack"), platform);
+    std::fs::write(game_path.join("ffxivgame.ver"), VERSION).unwrap();
+    std::fs::create_dir_all(game_path.join("movie/ffxiv")).unwrap();
+    std::fs::write(game_path.join("movie/ffxiv/00000.bk2"), [1, 2, 3]).unwrap();
+    files
+}
+
+fn assert_missing(source: &dyn SqPackSource, path: &str) {
+    assert!(!source.contains(path));
+    assert!(matches!(source.open(path), Err(Error::PathNotFound(p)) if p == path));
+}
+
+fn assert_library(library: &FfxivLibrary, files: &[(&str, Vec<u8>)], platform: PlatformId) {
+    assert_eq!(library.platform(), platform);
+    assert_files(library, files);
+    assert!(library.verify().unwrap().is_ok());
+}
+
+//////////////////////////////////////////
+
+#[test]
+fn directory_source() {
+    let dir = tempfile::tempdir().unwrap();
+    let files = write_game(dir.path(), PlatformId::Win32);
+
+    let source = DirectorySource::new(dir.path().join("sqpack"));
+    assert!(source.contains("ffxiv/0a0000.win32.index"));
+    assert_missing(&source, "ffxiv/0a0000.win32.index3");
+    // The game directory is only reachable through `open_game_file`
+    assert_missing(&source, "../ffxivgame.ver");
+    assert!(source.open_game_file("ffxivgame.ver").is_ok());
+    assert!(matches!(
+        source.open_game_file("sqpack/ffxiv/0a0000.win32.index"),
+        Err(Error::PathNotFound(_))
+    ));
+    assert!(source
+        .file_names("ffxiv")
+        .unwrap()
+        .contains(&"0a0000.win32.dat0".to_string()));
+    assert!(source.file_names("ex9").unwrap().is_empty());
+    assert_eq!(
+        source.directory(),
+        Some(dir.path().join("sqpack").as_path())
+    );
+
+    let library = FfxivLibrary::from_source(source);
+    assert_library(&library, &files, PlatformId::Win32);
+    assert_eq!(library.version().unwrap().to_string(), VERSION);
+}
+
+#[test]
+fn memory_source() {
+    let dir = tempfile::tempdir().unwrap();
+    let files = write_game(dir.path(), PlatformId::PS3);
+
+    let mut source = copy_to_memory(&dir.path().join("sqpack"));
+    assert!(matches!(
+        source.open_game_file("ffxivgame.ver"),
+        Err(Error::PathNotFound(_))
+    ));
+    source.insert_game_file("ffxivgame.ver", VERSION.as_bytes());
+    assert!(source.contains("ffxiv/0a0000.ps3.index2"));
+    assert!(source.directory().is_none());
+    assert_missing(&source, "ffxiv/0a0000.ps3.index3");
+
+    let library = FfxivLibrary::from_source(source);
+    assert_library(&library, &files, PlatformId::PS3);
+    assert_eq!(library.version().unwrap().to_string(), VERSION);
+}
+
+#[cfg(feature = "tar")]
+#[test]
+fn tar_source() {
+    use ffxiv_parser_lib::MemorySource;
+
+    let dir = tempfile::tempdir().unwrap();
+    let files = write_game(&dir.path().join("game"), PlatformId::Win32);
+
+    let mut builder = tar::Builder::new(Vec::new());
+    for (name, contents) in common::files_below(dir.path()) {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        builder
+            .append_data(&mut header, &name, &contents[..])
+            .unwrap();
+    }
+    let archive = builder.into_inner().unwrap();
+
+    let source = MemorySource::from_tar(&archive[..]).unwrap();
+    assert!(source.contains("ffxiv/0a0000.win32.index"));
+    assert!(!source.contains("ffxiv/00000.bk2"));
+
+    let library = FfxivLibrary::from_source(source);
+    assert_library(&library, &files, PlatformId::Win32);
+    assert_eq!(library.version().unwrap().to_string(), VERSION);
+}
+
+#[cfg(feature = "zip")]
+#[test]
+fn zip_source() {
+    use std::io::{Cursor, Write};
+
+    use ffxiv_parser_lib::ZipSource;
+
+    let dir = tempfile::tempdir().unwrap();
+    let files = write_game(&dir.path().join("game"), PlatformId::PS3);
+
+    let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, contents) in common::files_below(dir.path()) {
+        writer
+            .start_file(name, zip::write::SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(&contents).unwrap();
+    }
+    let archive = writer.finish().unwrap();
+
+    let source = ZipSource::new(archive).unwrap();
+    assert!(source.contains("ffxiv/0a0000.ps3.index"));
+    assert_missing(&source, "ffxiv/00000.bk2");
+
+    let library = FfxivLibrary::from_source(source);
+    assert_library(&library, &files, PlatformId::PS3);
+    assert_eq!(library.version().unwrap().to_string(), VERSION);
+}
+
+#[cfg(feature = "zip")]
+#[test]
+fn zip_source_without_indexes() {
+    use std::io::{Cursor, Write};
+
+    use ffxiv_parser_lib::ZipSource;
+
+    let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    writer
+        .start_file(
+            "game/movie/ffxiv/00000.bk2",
+            zip::write::SimpleFileOptions::default(),
+        )
+        .unwrap();
+    writer.write_all(&[1, 2, 3]).unwrap();
+    let archive = writer.finish().unwrap();
+
+    assert!(matches!(
+        ZipSource::new(archive),
+        Err(Error::PathNotFound(_))
+    ));
+}
@@ -0,0 +1,82 @@
+mod common;
+
+use ffxiv_parser_lib::{Error, FfxivLibrary};
+
+use common::{
+    assert_files, copy_to_memory, files_below, noise, write_fixture, PLATFORMS, TEXTURE_PATH,
+};
+
+//////////////////////////////////////////
+
+#[test]
+fn replace_and_restore() {
+    for platform in PLATFORMS {
+        let dir = tempfile::tempdir().unwrap();
+        let files = write_fixture(dir.path(), platform);
+        let original = files_below(dir.path());
+
+        let mut library = FfxivLibrary::with_platform(dir.path(), platform);
+        let root = b"EXLT,2\nItem,0\nAction,1\n".to_vec();
+        let texture = noise(30_000, 5);
+        library.replace_file("exd/root.exl", &root).unwrap();
+        library.replace_file(TEXTURE_PATH, &texture).unwrap();
+        // A second change to the same files keeps the first backups
+        library.replace_file("exd/root.exl", b"EXLT,2\n").unwrap();
+
+        let replaced = files
+            .iter()
+            .map(|(path, contents)| match *path {
+                "exd/root.exl" => (*path, b"EXLT,2\n".to_vec()),
+                TEXTURE_PATH => (*path, texture.clone()),
+                _ => (*path, contents.clone()),
+            })
+            .collect::<Vec<_>>();
+        assert_files(&library, &replaced);
+        assert!(library.verify().unwrap().is_ok());
+
+        assert!(library.restore_backup().unwrap());
+        assert_eq!(files_below(dir.path()), original);
+        assert!(!dir.path().join(".backup").exists());
+        assert_files(&library, &files);
+        assert!(!library.restore_backup().unwrap());
+    }
+}
+
+#[test]
+fn restore_keeps_other_files_in_the_journal_directory() {
+    for platform in PLATFORMS {
+        let dir = tempfile::tempdir().unwrap();
+        let sqpack_path = dir.path().join("sqpack");
+        let journal_path = dir.path().join("journal");
+        write_fixture(&sqpack_path, platform);
+        std::fs::create_dir_all(&journal_path).unwrap();
+        std::fs::write(journal_path.join("notes.txt"), "keep me").unwrap();
+
+        let mut library = FfxivLibrary::with_platform(&sqpack_path, platform);
+        library.set_journal_path(&journal_path);
+        library.replace_file("exd/root.exl", b"EXLT,2\n").unwrap();
+        assert!(library.restore_backup().unwrap());
+
+        let remaining = files_below(&journal_path);
+        assert_eq!(remaining, [("notes.txt".to_string(), b"keep me".to_vec())]);
+    }
+}
+
+#[test]
+fn replace_needs_an_existing_file_in_a_directory() {
+    for platform in PLATFORMS {
+        let dir = tempfile::tempdir().unwrap();
+        write_fixture(dir.path(), platform);
+        let mut library = FfxivLibrary::with_platform(dir.path(), platform);
+        assert!(matches!(
+            library.replace_file("exd/missing.exh", b""),
+            Err(Error::PathNotFound(_))
+        ));
+
+        let mut library = FfxivLibrary::from_source(copy_to_memory(dir.path()));
+        assert!(matches!(
+            library.replace_file("exd/root.exl", b""),
+            Err(Error::Unsupported(_))
+        ));
+    }
+}
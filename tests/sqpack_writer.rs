@@ -0,0 +1,94 @@
+mod common;
+
+use ffxiv_parser_lib::{Error, FfxivLibrary, SqPackWriter};
+
+use common::{
+    assert_files, entry, entry_type, noise_files, write_fixture, MODEL_ENTRY, MODEL_PATH,
+    PLATFORMS, STANDARD_ENTRY, TEXTURE_ENTRY, TEXTURE_PATH,
+};
+
+//////////////////////////////////////////
+
+#[test]
+fn round_trip() {
+    for platform in PLATFORMS {
+        let dir = tempfile::tempdir().unwrap();
+        let files = write_fixture(dir.path(), platform);
+
+        let library = FfxivLibrary::with_platform(dir.path(), platform);
+        assert_files(&library, &files);
+        assert!(library.verify().unwrap().is_ok());
+
+        assert_eq!(
+            entry_type(dir.path(), &library, "exd/root.exl"),
+            STANDARD_ENTRY
+        );
+        assert_eq!(
+            entry_type(dir.path(), &library, TEXTURE_PATH),
+            TEXTURE_ENTRY
+        );
+        assert_eq!(entry_type(dir.path(), &library, MODEL_PATH), MODEL_ENTRY);
+    }
+}
+
+#[test]
+fn unparsable_texture_falls_back_to_standard() {
+    for platform in PLATFORMS {
+        let dir = tempfile::tempdir().unwrap();
+        let mut writer = SqPackWriter::new(platform);
+        writer.add_file(TEXTURE_PATH, vec![1, 2, 3]).unwrap();
+        writer.write(dir.path()).unwrap();
+
+        let library = FfxivLibrary::with_platform(dir.path(), platform);
+        assert_eq!(&library.get_file(TEXTURE_PATH).unwrap()[..], [1, 2, 3]);
+        assert_eq!(
+            entry_type(dir.path(), &library, TEXTURE_PATH),
+            STANDARD_ENTRY
+        );
+    }
+}
+
+#[test]
+fn rolls_over_to_new_dat_files() {
+    for platform in PLATFORMS {
+        let dir = tempfile::tempdir().unwrap();
+        let files = noise_files(4, 3000);
+        let mut writer = SqPackWriter::new(platform).with_max_dat_size(4000);
+        for (path, contents) in &files {
+            writer.add_file(path, contents.clone()).unwrap();
+        }
+        writer.write(dir.path()).unwrap();
+
+        let library = FfxivLibrary::with_platform(dir.path(), platform);
+        let files = files
+            .iter()
+            .map(|(path, contents)| (path.as_str(), contents.clone()))
+            .collect::<Vec<_>>();
+        assert_files(&library, &files);
+        assert!(library.verify().unwrap().is_ok());
+
+        let data_file_ids = files
+            .iter()
+            .map(|(path, _)| entry(&library, path).1.data_file_id)
+            .collect::<Vec<_>>();
+        assert_eq!(data_file_ids, [0, 1, 2, 3]);
+        let last_dat = format!("ffxiv/0a0000.{}.dat3", platform.file_extension());
+        assert!(dir.path().join(last_dat).is_file());
+    }
+}
+
+#[test]
+fn fails_once_every_dat_file_is_full() {
+    for platform in PLATFORMS {
+        let dir = tempfile::tempdir().unwrap();
+        let mut writer = SqPackWriter::new(platform).with_max_dat_size(4000);
+        for (path, contents) in noise_files(9, 3000) {
+            writer.add_file(path, contents).unwrap();
+        }
+
+        assert!(matches!(
+            writer.write(dir.path()),
+            Err(Error::ArchiveFull(_))
+        ));
+    }
+}
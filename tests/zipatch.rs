@@ -0,0 +1,343 @@
+use std::{io::Write, path::Path};
+
+use byteorder::{BigEndian, LittleEndian, WriteBytesExt};
+use ffxiv_parser_lib::{Error, PlatformId, ZiPatchApplier, ZiPatchReader};
+use flate2::{write::DeflateEncoder, Compression};
+
+//////////////////////////////////////////
+
+const DAT_PATH: &str = "sqpack/ffxiv/0a0000.win32.dat0";
+const INDEX_PATH: &str = "sqpack/ffxiv/0a0000.win32.index";
+
+/// Builds a patch file in memory, one chunk at a time.
+struct PatchBuilder {
+    bytes: Vec<u8>,
+}
+
+impl PatchBuilder {
+    fn new() -> Self {
+        let mut builder = Self {
+            bytes: b"\x91ZIPATCH\r\n\x1A\n".to_vec(),
+        };
+        let mut header = Vec::new();
+        header.write_u32::<LittleEndian>(3 << 16).unwrap();
+        header.extend(b"DIFF");
+        header.write_u32::<BigEndian>(1).unwrap();
+        builder.chunk(b"FHDR", &header);
+        builder
+    }
+
+    fn chunk(&mut self, chunk_type: &[u8; 4], payload: &[u8]) -> &mut Self {
+        let crc = crc::Crc::<u32>::new(&crc::CRC_32_ISO_HDLC);
+        let mut digest = crc.digest();
+        digest.update(chunk_type);
+        digest.update(payload);
+
+        self.bytes
+            .write_u32::<BigEndian>(payload.len() as u32)
+            .unwrap();
+        self.bytes.extend(chunk_type);
+        self.bytes.extend(payload);
+        self.bytes
+            .write_u32::<BigEndian>(digest.finalize())
+            .unwrap();
+        self
+    }
+
+    fn apply_option(&mut self, option: u32, value: bool) -> &mut Self {
+        let mut payload = Vec::new();
+        payload.write_u32::<BigEndian>(option).unwrap();
+        payload.write_u32::<BigEndian>(4).unwrap();
+        payload.write_u32::<BigEndian>(value as u32).unwrap();
+        self.chunk(b"APLY", &payload)
+    }
+
+    fn add_directory(&mut self, name: &str) -> &mut Self {
+        let mut payload = Vec::new();
+        payload.write_u32::<BigEndian>(name.len() as u32).unwrap();
+        payload.extend(name.as_bytes());
+        self.chunk(b"ADIR", &payload)
+    }
+
+    fn sqpk(&mut self, command: u8, body: &[u8]) -> &mut Self {
+        let mut payload = Vec::new();
+        payload
+            .write_u32::<BigEndian>(body.len() as u32 + 5)
+            .unwrap();
+        payload.push(command);
+        payload.extend(body);
+        self.chunk(b"SQPK", &payload)
+    }
+
+    fn target_info(&mut self, platform: u16) -> &mut Self {
+        let mut body = vec![0; 3];
+        body.write_u16::<BigEndian>(platform).unwrap();
+        body.extend([0; 6]);
+        self.sqpk(b'T', &body)
+    }
+
+    /// An `A` command on `0a0000.dat0`, in 128-byte blocks.
+    fn add_data(&mut self, offset: u32, data: &[u8], delete_blocks: u32) -> &mut Self {
+        let mut body = vec![0; 3];
+        write_target(&mut body, 0);
+        body.write_u32::<BigEndian>(offset).unwrap();
+        body.write_u32::<BigEndian>(data.len() as u32 >> 7).unwrap();
+        body.write_u32::<BigEndian>(delete_blocks).unwrap();
+        body.extend(data);
+        self.sqpk(b'A', &body)
+    }
+
+    /// A `D` or `E` command on `0a0000.dat0`, in 128-byte blocks.
+    fn empty_blocks(&mut self, command: u8, offset: u32, block_count: u32) -> &mut Self {
+        let mut body = vec![0; 3];
+        write_target(&mut body, 0);
+        body.write_u32::<BigEndian>(offset).unwrap();
+        body.write_u32::<BigEndian>(block_count).unwrap();
+        body.write_u32::<BigEndian>(0).unwrap();
+        self.sqpk(command, &body)
+    }
+
+    /// An `H` command on `0a0000`.
+    fn header(&mut self, file_kind: u8, header_kind: u8, header: &[u8; 0x400]) -> &mut Self {
+        let mut body = vec![file_kind, header_kind, 0];
+        write_target(&mut body, 0);
+        body.extend(header);
+        self.sqpk(b'H', &body)
+    }
+
+    /// An `F` command, with `data` split into one compressed & one stored block.
+    fn file(&mut self, operation: u8, expansion_id: u16, path: &str, data: &[u8]) -> &mut Self {
+        let mut body = vec![operation, 0, 0];
+        body.write_u64::<BigEndian>(0).unwrap();
+        body.write_u64::<BigEndian>(data.len() as u64).unwrap();
+        body.write_u32::<BigEndian>(path.len() as u32 + 1).unwrap();
+        body.write_u16::<BigEndian>(expansion_id).unwrap();
+        body.extend([0, 0]);
+        body.extend(path.as_bytes());
+        body.push(0);
+
+        if !data.is_empty() {
+            let (first, second) = data.split_at(data.len() / 2);
+            let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(first).unwrap();
+            write_block(&mut body, &encoder.finish().unwrap(), first.len(), true);
+            write_block(&mut body, second, second.len(), false);
+        }
+        self.sqpk(b'F', &body)
+    }
+
+    fn finish(&mut self) -> Vec<u8> {
+        self.chunk(b"EOF_", &[]);
+        std::mem::take(&mut self.bytes)
+    }
+}
+
+fn write_target(body: &mut Vec<u8>, file_id: u32) {
+    body.write_u16::<BigEndian>(0x0a).unwrap();
+    body.write_u16::<BigEndian>(0).unwrap();
+    body.write_u32::<BigEndian>(file_id).unwrap();
+}
+
+fn write_block(body: &mut Vec<u8>, data: &[u8], decompressed_size: usize, compressed: bool) {
+    body.write_u32::<LittleEndian>(16).unwrap();
+    body.write_u32::<LittleEndian>(0).unwrap();
+    let compressed_size = if compressed { data.len() as u32 } else { 32000 };
+    body.write_u32::<LittleEndian>(compressed_size).unwrap();
+    body.write_u32::<LittleEndian>(decompressed_size as u32)
+        .unwrap();
+    body.extend(data);
+    body.resize(
+        body.len() + (16 + data.len()).next_multiple_of(0x80) - 16 - data.len(),
+        0,
+    );
+}
+
+fn apply(game_path: &Path, patch: &[u8]) -> Result<ZiPatchApplier, Error> {
+    let mut applier = ZiPatchApplier::new(game_path);
+    applier.apply(ZiPatchReader::new(patch)?)?;
+    Ok(applier)
+}
+
+/// The empty entry `D` & `E` leave at the start of the blocks they clear.
+fn empty_block(block_count: u32) -> Vec<u8> {
+    let mut block = Vec::new();
+    for value in [0x80, 0, 0, block_count - 1, 0] {
+        block.write_u32::<LittleEndian>(value).unwrap();
+    }
+    block.resize(block_count as usize * 0x80, 0);
+    block
+}
+
+//////////////////////////////////////////
+
+#[test]
+fn applies_data_commands() {
+    let dir = tempfile::tempdir().unwrap();
+    let header = [0x5A; 0x400];
+    let data = [7; 0x100];
+    let patch = PatchBuilder::new()
+        .add_directory("sqpack")
+        .header(b'D', b'V', &header)
+        .add_data(8, &data, 2)
+        .empty_blocks(b'D', 8, 1)
+        .empty_blocks(b'E', 12, 2)
+        .header(b'I', b'I', &header)
+        .finish();
+    apply(dir.path(), &patch).unwrap();
+
+    let mut expected = header.to_vec();
+    expected.extend(empty_block(1));
+    expected.extend(&data[0x80..]);
+    expected.extend([0; 0x100]);
+    expected.extend(empty_block(2));
+    assert_eq!(std::fs::read(dir.path().join(DAT_PATH)).unwrap(), expected);
+
+    let mut expected = vec![0; 0x400];
+    expected.extend(header);
+    assert_eq!(
+        std::fs::read(dir.path().join(INDEX_PATH)).unwrap(),
+        expected
+    );
+}
+
+#[test]
+fn applies_file_commands() {
+    let dir = tempfile::tempdir().unwrap();
+    let contents = (0..5000u32).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+    let patch = PatchBuilder::new()
+        .target_info(1)
+        .file(b'A', 0, "boot/ffxivboot.ver", &contents)
+        .file(b'M', 0, "game/movie/ffxiv", &[])
+        .finish();
+    let applier = apply(dir.path(), &patch).unwrap();
+
+    assert_eq!(applier.platform(), PlatformId::PS3);
+    let written = std::fs::read(dir.path().join("boot/ffxivboot.ver")).unwrap();
+    assert_eq!(written, contents);
+    assert!(dir.path().join("game/movie/ffxiv").is_dir());
+}
+
+#[test]
+fn ignores_missing_files_when_asked() {
+    let dir = tempfile::tempdir().unwrap();
+    let patch = PatchBuilder::new()
+        .file(b'D', 0, "sqpack/missing.dat0", &[])
+        .finish();
+    assert!(matches!(apply(dir.path(), &patch), Err(Error::Io(_))));
+
+    let patch = PatchBuilder::new()
+        .apply_option(1, true)
+        .file(b'D', 0, "sqpack/missing.dat0", &[])
+        .finish();
+    apply(dir.path(), &patch).unwrap();
+}
+
+#[test]
+fn remove_all_keeps_versions_and_opening_movies() {
+    let dir = tempfile::tempdir().unwrap();
+    let sqpack = dir.path().join("sqpack/ffxiv");
+    let movie = dir.path().join("movie/ffxiv");
+    std::fs::create_dir_all(&sqpack).unwrap();
+    std::fs::create_dir_all(&movie).unwrap();
+    for file_name in ["0a0000.win32.dat0", "0a0000.win32.index", "ffxivgame.var"] {
+        std::fs::write(sqpack.join(file_name), [1]).unwrap();
+    }
+    for i in 0..6 {
+        std::fs::write(movie.join(format!("0000{}.bk2", i)), [1]).unwrap();
+    }
+
+    let patch = PatchBuilder::new().file(b'R', 0, "", &[]).finish();
+    apply(dir.path(), &patch).unwrap();
+
+    let file_names = |path: &Path| {
+        let mut file_names = std::fs::read_dir(path)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        file_names.sort();
+        file_names
+    };
+    assert_eq!(file_names(&sqpack), ["ffxivgame.var"]);
+    assert_eq!(
+        file_names(&movie),
+        ["00000.bk2", "00001.bk2", "00002.bk2", "00003.bk2"]
+    );
+}
+
+#[test]
+fn rejects_paths_outside_of_the_game_directory() {
+    let dir = tempfile::tempdir().unwrap();
+    let game_path = dir.path().join("game");
+    for patch in [
+        PatchBuilder::new().add_directory("../outside").finish(),
+        PatchBuilder::new().add_directory("/outside").finish(),
+        PatchBuilder::new()
+            .file(b'A', 0, "sqpack/../../outside.txt", &[1, 2, 3])
+            .finish(),
+    ] {
+        assert!(matches!(
+            apply(&game_path, &patch),
+            Err(Error::UnknownValue { .. })
+        ));
+    }
+    assert!(!dir.path().join("outside").exists());
+    assert!(!dir.path().join("outside.txt").exists());
+}
+
+#[test]
+fn rejects_lengths_past_the_chunk() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut body = vec![b'A', 0, 0];
+    body.write_u64::<BigEndian>(0).unwrap();
+    body.write_u64::<BigEndian>(0).unwrap();
+    body.write_u32::<BigEndian>(u32::MAX).unwrap();
+    body.write_u16::<BigEndian>(0).unwrap();
+    body.extend([0, 0]);
+    body.extend(b"short\0");
+    let patch = PatchBuilder::new().sqpk(b'F', &body).finish();
+
+    assert!(matches!(
+        apply(dir.path(), &patch),
+        Err(Error::Truncated { .. })
+    ));
+}
+
+#[test]
+fn rejects_bad_checksums() {
+    let mut patch = PatchBuilder::new().add_directory("sqpack").finish();
+    let length = patch.len();
+    patch[length - 20] ^= 1;
+
+    let chunks = ZiPatchReader::new(&patch[..]).unwrap();
+    assert!(chunks
+        .into_iter()
+        .any(|chunk| matches!(chunk, Err(Error::InvalidChecksum { .. }))));
+}
+
+#[test]
+fn rejects_block_sizes_past_the_chunk() {
+    let dir = tempfile::tempdir().unwrap();
+    let file_command = |header_size: u32, compressed_size: u32| {
+        let mut body = vec![b'A', 0, 0];
+        body.write_u64::<BigEndian>(0).unwrap();
+        body.write_u64::<BigEndian>(3).unwrap();
+        body.write_u32::<BigEndian>(6).unwrap();
+        body.write_u16::<BigEndian>(0).unwrap();
+        body.extend([0, 0]);
+        body.extend(b"a.txt\0");
+        for value in [header_size, 0, compressed_size, 3] {
+            body.write_u32::<LittleEndian>(value).unwrap();
+        }
+        body.extend([1, 2, 3]);
+        PatchBuilder::new().sqpk(b'F', &body).finish()
+    };
+
+    // The header size only decides the padding, which may be cut short by the chunk's end
+    apply(dir.path(), &file_command(u32::MAX, 32000)).unwrap();
+    assert_eq!(std::fs::read(dir.path().join("a.txt")).unwrap(), [1, 2, 3]);
+
+    assert!(matches!(
+        apply(dir.path(), &file_command(16, u32::MAX - 1)),
+        Err(Error::Truncated { .. })
+    ));
+}